@@ -0,0 +1,269 @@
+//! This module contains functions for anchoring notes to a range of lines in
+//! a file, e.g. `--on src/lib.rs:40-55`.
+//!
+//! The anchored lines are stored with the note. When the file is edited and
+//! the lines move, the anchor is re-located by finding the part of the file
+//! that best matches the stored lines.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use note::Note;
+use store::{lock_notes, open_store};
+
+/// The smallest fraction of the anchored lines that must be found for a
+/// moved anchor to be re-located.
+const MIN_SIMILARITY: f64 = 0.5;
+
+/// A range of lines in the file a note is tacked onto.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Anchor {
+    /// First line of the range, starting from 1.
+    pub start: usize,
+    /// Last line of the range, inclusive.
+    pub end: usize,
+    /// A hash of the anchored lines, ignoring leading and trailing whitespace.
+    pub fingerprint: String,
+    /// The anchored lines, as they were when the anchor was last located.
+    pub lines: Vec<String>,
+}
+
+impl Anchor {
+    /// Creates an anchor from lines of a file.
+    pub fn new(file: &Path, start: usize, end: usize) -> Result<Anchor, Box<dyn Error>> {
+        let contents = fs::read_to_string(file)
+            .map_err(|_| format!("Could not read '{}'.", file.display()))?;
+        let file_lines: Vec<&str> = contents.lines().collect();
+        if start == 0 || start > end || end > file_lines.len() {
+            return Err(From::from(format!(
+                "Lines {}-{} are outside of '{}', which has {} lines.",
+                start,
+                end,
+                file.display(),
+                file_lines.len()
+            )));
+        }
+
+        Ok(Anchor::from_lines(&file_lines[start - 1..end], start))
+    }
+
+    fn from_lines(lines: &[&str], start: usize) -> Anchor {
+        Anchor {
+            start,
+            end: start + lines.len() - 1,
+            fingerprint: fingerprint(lines),
+            lines: lines.iter().map(|l| String::from(*l)).collect(),
+        }
+    }
+
+    /// Finds where the anchored lines are in the current contents of the file.
+    ///
+    /// Returns `None` if the lines haven't moved or can't be found, or the
+    /// anchor's range is invalid, as it may be in an imported or hand-edited
+    /// note.
+    pub fn relocate(&self, contents: &str) -> Option<Anchor> {
+        if self.start == 0 || self.start > self.end {
+            return None;
+        }
+        let file_lines: Vec<&str> = contents.lines().collect();
+        if self.end <= file_lines.len()
+            && fingerprint(&file_lines[self.start - 1..self.end]) == self.fingerprint
+        {
+            return None;
+        }
+
+        self.locate(contents).map(|(_, anchor)| anchor)
+    }
+
+    /// Finds the lines in some text that best match the anchored lines, along
+    /// with the fraction of the anchored lines found there.
+    pub fn locate(&self, contents: &str) -> Option<(f64, Anchor)> {
+        let file_lines: Vec<&str> = contents.lines().collect();
+        let len = self.lines.len();
+        let mut best: Option<(f64, usize, usize)> = None;
+        // Lines may have been added to or removed from the range itself.
+        for window in len.saturating_sub(1).max(1)..=len + 1 {
+            if window > file_lines.len() {
+                break;
+            }
+            for start in 0..=file_lines.len() - window {
+                let score = similarity(&self.lines, &file_lines[start..start + window]);
+                let distance = (start + 1).abs_diff(self.start);
+                let better = match best {
+                    None => true,
+                    Some((best_score, best_start, _)) => {
+                        score > best_score
+                            || (score == best_score
+                                && distance < (best_start + 1).abs_diff(self.start))
+                    }
+                };
+                if better {
+                    best = Some((score, start, window));
+                }
+            }
+        }
+        match best {
+            Some((score, start, window)) if score >= MIN_SIMILARITY => Some((
+                score,
+                Anchor::from_lines(&file_lines[start..start + window], start + 1),
+            )),
+            _ => None,
+        }
+    }
+
+    /// The anchored lines with their line numbers.
+    pub fn snippet(&self) -> String {
+        let width = self.end.to_string().len();
+        self.lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| format!("{:>width$} | {}", self.start + i, line, width = width))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+/// First and last line numbers of a range, inclusive.
+pub type LineRange = (usize, usize);
+
+/// Splits a `--on` argument into a path and an optional line range, given as
+/// `path:line` or `path:start-end`.
+pub fn split_line_range(on: &str) -> Result<(&str, Option<LineRange>), Box<dyn Error>> {
+    let (path, range) = match on.rfind(':') {
+        Some(i) if !Path::new(on).exists() => (&on[..i], &on[i + 1..]),
+        _ => return Ok((on, None)),
+    };
+    let parse = |n: &str| {
+        n.trim()
+            .parse::<usize>()
+            .map_err(|_| format!("'{}' is not a valid line range.", range))
+    };
+    let (start, end) = match range.find('-') {
+        Some(i) => (parse(&range[..i])?, parse(&range[i + 1..])?),
+        None => {
+            let line = parse(range)?;
+            (line, line)
+        }
+    };
+
+    Ok((path, Some((start, end))))
+}
+
+/// A stable hash of lines, ignoring leading and trailing whitespace.
+fn fingerprint(lines: &[&str]) -> String {
+    // 64 bit FNV-1a, which unlike `DefaultHasher` is the same everywhere.
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for line in lines {
+        for byte in line.trim().bytes().chain(Some(b'\n')) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    format!("{:016x}", hash)
+}
+
+/// The fraction of the anchored lines found in a candidate range of lines.
+fn similarity(anchored: &[String], candidate: &[&str]) -> f64 {
+    let mut available: HashMap<&str, usize> = HashMap::new();
+    for line in candidate {
+        *available.entry(line.trim()).or_insert(0) += 1;
+    }
+    let mut found = 0;
+    for line in anchored {
+        if let Some(count) = available.get_mut(line.trim()) {
+            if *count > 0 {
+                *count -= 1;
+                found += 1;
+            }
+        }
+    }
+
+    found as f64 / anchored.len().max(candidate.len()) as f64
+}
+
+/// Re-locates the anchors of notes whose files have changed, saving any that
+/// have moved.
+pub fn relocate_anchors(notes: &mut [Note], tacked_dir: &Path) -> Result<(), Box<dyn Error>> {
+    let project_dir = tacked_dir.parent().unwrap_or(tacked_dir);
+    let mut moved: Vec<(String, Anchor)> = Vec::new();
+    for note in notes.iter_mut() {
+        let new_anchor = match (&note.on, &note.anchor) {
+            (Some(on), Some(anchor)) => match fs::read_to_string(project_dir.join(on)) {
+                Ok(contents) => anchor.relocate(&contents),
+                Err(_) => None,
+            },
+            _ => None,
+        };
+        if let Some(anchor) = new_anchor {
+            moved.push((note.id.clone(), anchor.clone()));
+            note.anchor = Some(anchor);
+        }
+    }
+    if !moved.is_empty() {
+        let _lock = lock_notes(tacked_dir)?;
+        let mut store = open_store(tacked_dir)?;
+        let ids: Vec<String> = moved.iter().map(|(id, _)| id.clone()).collect();
+        let mut stored = store.get(&ids)?;
+        for note in stored.iter_mut() {
+            if let Some((_, anchor)) = moved.iter().find(|(id, _)| *id == note.id) {
+                note.anchor = Some(anchor.clone());
+            }
+        }
+        store.update_all(&stored)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn parse_line_ranges() {
+        assert_eq!(
+            split_line_range("src/lib.rs").unwrap(),
+            ("src/lib.rs", None)
+        );
+        assert_eq!(
+            split_line_range("src/lib.rs:40-55").unwrap(),
+            ("src/lib.rs", Some((40, 55)))
+        );
+        assert_eq!(
+            split_line_range("src/lib.rs:7").unwrap(),
+            ("src/lib.rs", Some((7, 7)))
+        );
+        assert!(split_line_range("src/lib.rs:a-b").is_err());
+    }
+
+    #[test]
+    fn relocate_moved_lines() {
+        let temp_dir = TempDir::new("anchor_test").expect("Could not create temp directory.");
+        let file = temp_dir.path().join("code.rs");
+        fs::write(
+            &file,
+            "fn a() {}\nfor i in 0..n {\n    for j in 0..n {\n    }\n}\n",
+        )
+        .unwrap();
+        let anchor = Anchor::new(&file, 2, 5).unwrap();
+        assert_eq!(anchor.lines[0], "for i in 0..n {");
+        assert!(anchor
+            .relocate(&fs::read_to_string(&file).unwrap())
+            .is_none());
+        let edited =
+            "use x;\n\nfn a() {}\n\nfor i in 0..n {\n  for j in 0..n {\n    x();\n  }\n}\n";
+        let moved = anchor.relocate(edited).unwrap();
+        assert_eq!((moved.start, moved.end), (5, 9));
+        assert!(anchor.relocate("something else entirely\n").is_none());
+        assert!(Anchor::new(&file, 4, 9).is_err());
+        let mut invalid = anchor.clone();
+        invalid.start = 0;
+        assert!(invalid.relocate(edited).is_none());
+        invalid.start = 6;
+        assert!(invalid.relocate(edited).is_none());
+    }
+}
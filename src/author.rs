@@ -0,0 +1,118 @@
+//! This module works out who is writing notes.
+//!
+//! The author's name and email are taken from `git config`, so notes are
+//! signed the same way as commits. Without git, `author` and `email` in
+//! `.tacked/config.json` are used, and then the name in `$USER`.
+
+use std::env;
+use std::path::Path;
+
+use config::{load_config, Config};
+use note::Note;
+use project::git_output;
+
+/// The name and email of the person writing notes.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Author {
+    pub name: Option<String>,
+    pub email: Option<String>,
+}
+
+impl Author {
+    /// Marks a note as written by this author.
+    pub fn sign(&self, note: &mut Note) {
+        note.user = self.name.clone();
+        note.email = self.email.clone();
+    }
+
+    /// Whether nothing is known about this author.
+    pub fn is_unknown(&self) -> bool {
+        self.name.is_none() && self.email.is_none()
+    }
+
+    /// Whether a note was written by this author, going by either their name
+    /// or their email, as notes may only have one of them.
+    pub fn wrote(&self, note: &Note) -> bool {
+        self.name
+            .iter()
+            .chain(self.email.iter())
+            .any(|who| is_by(note, who))
+    }
+}
+
+/// Finds the author of new notes in a project.
+pub fn current_author(tacked_dir: &Path) -> Author {
+    let dir = tacked_dir.parent().unwrap_or(tacked_dir);
+    let git_value = |key| {
+        git_output(dir, &["config", "--get", key])
+            .map(|value| String::from(value.trim()))
+            .filter(|value| !value.is_empty())
+    };
+    let from_git = Author {
+        name: git_value("user.name"),
+        email: git_value("user.email"),
+    };
+    let config = load_config(tacked_dir).unwrap_or_default();
+
+    choose_author(from_git, &config, env::var("USER").ok())
+}
+
+/// Fills in the parts of the author that git doesn't know from the project's
+/// config, and then from the user name.
+fn choose_author(from_git: Author, config: &Config, user: Option<String>) -> Author {
+    Author {
+        name: from_git.name.or_else(|| config.author.clone()).or(user),
+        email: from_git.email.or_else(|| config.email.clone()),
+    }
+}
+
+/// Whether a note was written by someone, given by name or email. Case is
+/// ignored.
+pub fn is_by(note: &Note, who: &str) -> bool {
+    let who = who.to_lowercase();
+    let matches = |value: &Option<String>| {
+        value
+            .as_ref()
+            .is_some_and(|value| value.to_lowercase() == who)
+    };
+    matches(&note.user) || matches(&note.email)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn choose_and_match_authors() {
+        let config = Config {
+            author: Some(String::from("Ada Lovelace")),
+            email: Some(String::from("ada@example.com")),
+            ..Default::default()
+        };
+        let from_git = Author {
+            name: Some(String::from("Ada L")),
+            email: None,
+        };
+        let author = choose_author(from_git, &config, Some(String::from("root")));
+        assert_eq!(author.name.as_deref(), Some("Ada L"));
+        assert_eq!(author.email.as_deref(), Some("ada@example.com"));
+        let fallback = choose_author(Author::default(), &Config::default(), None);
+        assert_eq!(fallback, Author::default());
+        let user = choose_author(
+            Author::default(),
+            &Config::default(),
+            Some(String::from("root")),
+        );
+        assert_eq!(user.name.as_deref(), Some("root"));
+        let mut note = Note::new(String::from("Note."));
+        author.sign(&mut note);
+        assert!(is_by(&note, "ADA@example.com"));
+        assert!(is_by(&note, "ada l"));
+        assert!(!is_by(&note, "root"));
+        let mut unsigned = Note::new(String::from("Before emails."));
+        unsigned.user = Some(String::from("Ada L"));
+        assert!(author.wrote(&unsigned));
+        assert!(!user.wrote(&unsigned));
+        assert!(fallback.is_unknown());
+    }
+}
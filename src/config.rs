@@ -0,0 +1,45 @@
+//! This module contains the per-project configuration stored in
+//! `.tacked/config.json`.
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use serde_json;
+
+use store::{write_atomic, StoreKind};
+
+/// Project settings. Projects created before `config.json` existed use the
+/// defaults.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub store: StoreKind,
+    /// Words marking comments that `scan` turns into to do items. If empty,
+    /// `TODO`, `FIXME` and `XXX` are used.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scan_markers: Vec<String>,
+    /// Name to sign notes with when git doesn't know it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    /// Email to sign notes with when git doesn't know it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+}
+
+/// Loads the configuration from the `.tacked` directory.
+pub fn load_config(tacked_dir: &Path) -> Result<Config, Box<dyn Error>> {
+    let config_path = tacked_dir.join("config.json");
+    if config_path.exists() {
+        let config_string = fs::read_to_string(&config_path)?;
+        Ok(serde_json::from_str(&config_string)?)
+    } else {
+        Ok(Config::default())
+    }
+}
+
+/// Writes the configuration to the `.tacked` directory.
+pub fn save_config(config: &Config, tacked_dir: &Path) -> Result<(), Box<dyn Error>> {
+    let config_json = serde_json::to_string_pretty(config)?;
+    write_atomic(&tacked_dir.join("config.json"), config_json.as_bytes())
+}
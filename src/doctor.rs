@@ -0,0 +1,370 @@
+//! This module contains the `doctor` and `relink` subcommands, which find
+//! notes tacked onto files that no longer exist and move them to where the
+//! files have gone.
+//!
+//! New locations are found from renames recorded by git, by searching the
+//! project for the lines a note is anchored to, or by looking for a single
+//! file with the same name. `doctor` also lists links between notes that
+//! lead nowhere.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono;
+use clap;
+
+use anchor::Anchor;
+use global::{is_global, resolve_tacked_dir};
+use init::ask_yes_no;
+use links::dangling_links;
+use note::{get_notes, Note};
+use project::{git_output, project_files};
+use store::{lock_notes, open_store};
+use tags::add_tags;
+
+/// Tag given to notes that are archived because their file is gone.
+/// Archived notes are ignored by `doctor`.
+pub const ARCHIVED_TAG: &str = "archived";
+
+/// A note whose `on` path no longer exists.
+#[derive(Debug)]
+pub struct Orphan {
+    pub note: Note,
+    pub proposal: Option<Proposal>,
+}
+
+/// A suggested new location for an orphaned note.
+#[derive(Debug)]
+pub struct Proposal {
+    pub on: PathBuf,
+    pub anchor: Option<Anchor>,
+    pub reason: Reason,
+}
+
+/// How a new location was found.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Reason {
+    GitRename,
+    Content,
+    SameName,
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let description = match self {
+            Reason::GitRename => "renamed in git",
+            Reason::Content => "anchored lines found",
+            Reason::SameName => "only file with the same name",
+        };
+        write!(f, "{}", description)
+    }
+}
+
+/// Main entry point for the `doctor` subcommand.
+pub fn run_doctor(input: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let tacked_dir = resolve_tacked_dir(input)?;
+    let orphans = find_orphans(&tacked_dir)?;
+    if orphans.is_empty() {
+        println!("All notes are tacked onto files that exist.");
+    } else {
+        print_orphans(&orphans);
+        println!("Run `tack relink` to move notes to the suggested files.");
+    }
+    let notes = get_notes(&tacked_dir)?;
+    for (note, link) in dangling_links(&notes) {
+        println!("Link [[{}]] leads nowhere: {}", link, note.oneliner());
+    }
+
+    Ok(())
+}
+
+/// Main entry point for the `relink` subcommand.
+pub fn run_relink(input: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let tacked_dir = resolve_tacked_dir(input)?;
+    let archive = input.is_present("archive");
+    let mut orphans = find_orphans(&tacked_dir)?;
+    if !archive {
+        orphans.retain(|o| o.proposal.is_some());
+    }
+    if orphans.is_empty() {
+        println!("No notes to relink.");
+        return Ok(());
+    }
+    if !input.is_present("yes") {
+        print_orphans(&orphans);
+        if !ask_yes_no(&format!("Change these {} note(s)?", orphans.len())) {
+            return Ok(());
+        }
+    }
+    let (relinked, archived) = relink(orphans, archive, &tacked_dir)?;
+    println!("Relinked {} note(s), archived {}.", relinked, archived);
+
+    Ok(())
+}
+
+fn print_orphans(orphans: &[Orphan]) {
+    for orphan in orphans.iter() {
+        let on = orphan.note.on.as_ref().expect("Orphans are on a path.");
+        println!("On missing {}: {}", on.display(), orphan.note.oneliner());
+        match orphan.proposal {
+            Some(ref proposal) => {
+                println!("    -> {} ({})", proposal.on.display(), proposal.reason)
+            }
+            None => println!("    no new location found"),
+        }
+    }
+}
+
+/// Finds the notes tacked onto paths that no longer exist, suggesting new
+/// locations where possible.
+pub fn find_orphans(tacked_dir: &Path) -> Result<Vec<Orphan>, Box<dyn Error>> {
+    let project_dir = tacked_dir.parent().unwrap_or(tacked_dir);
+    let archived = String::from(ARCHIVED_TAG);
+    let notes = open_store(tacked_dir)?.query(&|n| {
+        n.on.as_ref()
+            .is_some_and(|on| !project_dir.join(on).exists())
+            && !n.tags.contains(&archived)
+    })?;
+    if notes.is_empty() {
+        return Ok(Vec::new());
+    }
+    // Global notes aren't in a project, so there is nowhere to search.
+    let (renames, files) = if is_global(tacked_dir) {
+        (HashMap::new(), Vec::new())
+    } else {
+        (git_renames(project_dir), project_files(project_dir))
+    };
+    let orphans = notes
+        .into_iter()
+        .map(|note| {
+            let proposal = propose(&note, &renames, &files, project_dir);
+            Orphan { note, proposal }
+        })
+        .collect();
+
+    Ok(orphans)
+}
+
+/// Moves orphaned notes to their proposed locations, optionally archiving
+/// those without one. Returns the number relinked and archived.
+fn relink(
+    orphans: Vec<Orphan>,
+    archive: bool,
+    tacked_dir: &Path,
+) -> Result<(usize, usize), Box<dyn Error>> {
+    let _lock = lock_notes(tacked_dir)?;
+    let mut store = open_store(tacked_dir)?;
+    let ids: Vec<String> = orphans.iter().map(|o| o.note.id.clone()).collect();
+    let mut proposals: HashMap<String, Option<Proposal>> = orphans
+        .into_iter()
+        .map(|o| (o.note.id, o.proposal))
+        .collect();
+    let mut notes = store.get(&ids)?;
+    let (mut relinked, mut archived) = (0, 0);
+    for note in notes.iter_mut() {
+        match proposals.remove(&note.id) {
+            Some(Some(proposal)) => {
+                note.on = Some(proposal.on);
+                note.anchor = proposal.anchor;
+                relinked += 1;
+            }
+            _ if archive => {
+                add_tags(note, vec![String::from(ARCHIVED_TAG)]);
+                archived += 1;
+            }
+            _ => continue,
+        }
+        note.modified = Some(chrono::Local::now());
+    }
+    store.update_all(&notes)?;
+
+    Ok((relinked, archived))
+}
+
+/// Suggests where an orphaned note's file has gone.
+fn propose(
+    note: &Note,
+    renames: &HashMap<PathBuf, PathBuf>,
+    files: &[PathBuf],
+    project_dir: &Path,
+) -> Option<Proposal> {
+    let on = note.on.as_ref()?;
+    if let Some(new_on) = follow_renames(on, renames) {
+        if project_dir.join(&new_on).exists() {
+            let anchor = note.anchor.as_ref().and_then(|a| {
+                fs::read_to_string(project_dir.join(&new_on))
+                    .ok()
+                    .and_then(|contents| a.locate(&contents))
+                    .map(|(_, found)| found)
+            });
+            return Some(Proposal {
+                on: new_on,
+                anchor: anchor.or_else(|| note.anchor.clone()),
+                reason: Reason::GitRename,
+            });
+        }
+    }
+    if let Some(ref anchor) = note.anchor {
+        let mut best: Option<(f64, &PathBuf, Anchor)> = None;
+        for file in files.iter() {
+            let contents = match fs::read_to_string(project_dir.join(file)) {
+                Ok(contents) => contents,
+                Err(_) => continue,
+            };
+            if let Some((score, found)) = anchor.locate(&contents) {
+                if best.as_ref().is_none_or(|(s, _, _)| score > *s) {
+                    best = Some((score, file, found));
+                }
+            }
+        }
+        if let Some((_, file, found)) = best {
+            return Some(Proposal {
+                on: file.clone(),
+                anchor: Some(found),
+                reason: Reason::Content,
+            });
+        }
+    }
+    let mut same_name = files.iter().filter(|f| f.file_name() == on.file_name());
+    match (same_name.next(), same_name.next()) {
+        (Some(file), None) => Some(Proposal {
+            on: file.clone(),
+            anchor: None,
+            reason: Reason::SameName,
+        }),
+        _ => None,
+    }
+}
+
+/// Follows a path through a series of renames, also handling files in
+/// directories that were renamed.
+fn follow_renames(on: &Path, renames: &HashMap<PathBuf, PathBuf>) -> Option<PathBuf> {
+    let mut current = on.to_path_buf();
+    // Bounded, in case the renames go round in a circle.
+    for _ in 0..renames.len() {
+        match renames.get(&current) {
+            Some(new) => current = new.clone(),
+            None => break,
+        }
+    }
+    if current != on {
+        return Some(current);
+    }
+    // A directory was renamed if the files in it were.
+    renames.iter().find_map(|(old, new)| {
+        let rest = old.strip_prefix(on).ok()?;
+        if rest.as_os_str().is_empty() || !new.ends_with(rest) {
+            return None;
+        }
+        let depth = rest.components().count();
+        new.ancestors().nth(depth).map(Path::to_path_buf)
+    })
+}
+
+/// Collects the renames recorded by git, both committed and staged, mapping
+/// old paths to new paths relative to the project directory.
+///
+/// Returns no renames if the project isn't in a git repository.
+fn git_renames(project_dir: &Path) -> HashMap<PathBuf, PathBuf> {
+    let mut renames = HashMap::new();
+    let logged = git_output(
+        project_dir,
+        &[
+            "log",
+            "-M",
+            "--diff-filter=R",
+            "--name-status",
+            "--format=",
+            "--relative",
+        ],
+    );
+    let staged = git_output(
+        project_dir,
+        &[
+            "diff",
+            "--cached",
+            "-M",
+            "--diff-filter=R",
+            "--name-status",
+            "--relative",
+        ],
+    );
+    // The log is newest first, so it is applied in reverse.
+    let mut pairs = parse_renames(&logged.unwrap_or_default());
+    pairs.reverse();
+    pairs.extend(parse_renames(&staged.unwrap_or_default()));
+    for (old, new) in pairs {
+        renames.insert(old, new);
+    }
+
+    renames
+}
+
+/// Parses `--name-status` lines such as `R097\told/path\tnew/path`.
+fn parse_renames(output: &str) -> Vec<(PathBuf, PathBuf)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            match (fields.next(), fields.next(), fields.next()) {
+                (Some(status), Some(old), Some(new)) if status.starts_with('R') => {
+                    Some((PathBuf::from(old), PathBuf::from(new)))
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use note::{create_note, get_notes};
+    use tempdir::TempDir;
+
+    #[test]
+    fn follow_git_renames() {
+        let output = "R100\tsrc/a.rs\tsrc/b.rs\n\nM\tREADME.md\nR090\tdocs/x.md\tguide/x.md\n";
+        let renames: HashMap<PathBuf, PathBuf> = parse_renames(output).into_iter().collect();
+        assert_eq!(renames.len(), 2);
+        assert_eq!(
+            follow_renames(Path::new("src/a.rs"), &renames),
+            Some(PathBuf::from("src/b.rs"))
+        );
+        assert_eq!(
+            follow_renames(Path::new("docs"), &renames),
+            Some(PathBuf::from("guide"))
+        );
+        assert_eq!(follow_renames(Path::new("src/c.rs"), &renames), None);
+    }
+
+    #[test]
+    fn relink_moved_file() {
+        let temp_dir = TempDir::new("doctor_test").expect("Could not create temp directory.");
+        let tacked_path = temp_dir.path().join(".tacked");
+        fs::create_dir(tacked_path.clone()).unwrap();
+        let old = temp_dir.path().join("old.rs");
+        fs::write(&old, "fn main() {\n    slow();\n}\n").unwrap();
+        let on = format!("{}:1-3", old.display());
+        create_note(String::from("Slow."), Some(&on), None, &[], &tacked_path).unwrap();
+        let gone = temp_dir.path().join("gone.txt");
+        fs::write(&gone, "").unwrap();
+        let gone = gone.to_str().unwrap();
+        create_note(String::from("Gone."), Some(gone), None, &[], &tacked_path).unwrap();
+        fs::create_dir(temp_dir.path().join("src")).unwrap();
+        fs::rename(&old, temp_dir.path().join("src").join("new.rs")).unwrap();
+        fs::remove_file(gone).unwrap();
+        let orphans = find_orphans(&tacked_path).unwrap();
+        assert_eq!(orphans.len(), 2);
+        let (relinked, archived) = relink(orphans, true, &tacked_path).unwrap();
+        assert_eq!((relinked, archived), (1, 1));
+        let notes = get_notes(&tacked_path).unwrap();
+        let moved = notes.iter().find(|n| n.content == "Slow.").unwrap();
+        assert_eq!(moved.on, Some(PathBuf::from("src/new.rs")));
+        assert_eq!(moved.anchor.as_ref().unwrap().start, 1);
+        assert!(find_orphans(&tacked_path).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,181 @@
+//! This module contains due dates of to do items and the `agenda` subcommand.
+//!
+//! Due dates can be given as `YYYY-MM-DD`, as `today` or `tomorrow`, as
+//! `in 3 days` or `in 2 weeks`, or as the name of a weekday such as `fri`,
+//! which means the next one after today.
+
+use std::error::Error;
+use std::io::{self, IsTerminal};
+
+use chrono::{Datelike, Duration, Local, NaiveDate, Weekday};
+use clap;
+
+use global::resolve_tacked_dir;
+use note::{get_notes, Note};
+
+/// How far ahead the agenda looks, including today.
+const AGENDA_DAYS: i64 = 7;
+
+/// Main entry point for the `agenda` subcommand.
+pub fn run_agenda(input: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let tacked_dir = resolve_tacked_dir(input)?;
+    let notes = get_notes(&tacked_dir)?;
+    let lines = agenda(&notes, today(), io::stdout().is_terminal());
+    if lines.is_empty() {
+        println!("Nothing due this week.");
+    } else {
+        println!("{}", lines.join("\n"));
+    }
+
+    Ok(())
+}
+
+/// The current local date.
+pub fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+/// Parses a due date, relative to today.
+pub fn parse_due(text: &str, today: NaiveDate) -> Result<NaiveDate, String> {
+    let text = text.trim().to_lowercase();
+    if let Ok(date) = NaiveDate::parse_from_str(&text, "%Y-%m-%d") {
+        return Ok(date);
+    }
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let date = match words[..] {
+        ["today"] => Some(today),
+        ["tomorrow"] => Some(today + Duration::days(1)),
+        ["in", count, unit] => match (count.parse::<i64>(), unit) {
+            (Ok(n), "day") | (Ok(n), "days") => Duration::try_days(n),
+            (Ok(n), "week") | (Ok(n), "weeks") => Duration::try_weeks(n),
+            _ => None,
+        }
+        .and_then(|ahead| today.checked_add_signed(ahead)),
+        [day] | ["next", day] => day.parse::<Weekday>().ok().map(|weekday| {
+            let ahead = weekday.num_days_from_monday() as i64
+                - today.weekday().num_days_from_monday() as i64;
+            today + Duration::days((ahead + 6).rem_euclid(7) + 1)
+        }),
+        _ => None,
+    };
+
+    date.ok_or_else(|| {
+        format!(
+            "Could not understand due date '{}'. Use YYYY-MM-DD, today, tomorrow, \
+             'in 3 days', 'in 2 weeks' or a weekday such as fri.",
+            text
+        )
+    })
+}
+
+/// Describes when a to do item is due, in red if it's overdue and
+/// `highlight` is set.
+pub fn due_label(due: NaiveDate, overdue: bool, highlight: bool) -> String {
+    if !overdue {
+        return format!("due {}", due.format("%a %Y-%m-%d"));
+    }
+    let label = format!("overdue since {}", due.format("%a %Y-%m-%d"));
+    if highlight {
+        format!("\x1b[1;31m{}\x1b[0m", label)
+    } else {
+        label
+    }
+}
+
+/// Whether a note is an open to do item that is past its due date.
+pub fn is_overdue(note: &Note, today: NaiveDate) -> bool {
+    note.todo.is_some_and(|(_, done)| !done) && note.due.is_some_and(|due| due < today)
+}
+
+/// The to do list line for a note, with its due date and recurrence if it
+/// has them.
+pub fn todo_line(note: &Note, today: NaiveDate, highlight: bool) -> Option<String> {
+    let (_, mut line) = note.todo_item()?;
+    if let Some(due) = note.due {
+        let overdue = is_overdue(note, today);
+        line.push_str(&format!(" {}", due_label(due, overdue, highlight)));
+    }
+    if let Some(ref recur) = note.recur {
+        line.push_str(&format!(" (repeats {})", recur));
+    }
+
+    Some(line)
+}
+
+/// Lists the open to do items that are overdue, due today and due in the rest
+/// of the week, each under a heading. Sections with nothing due are left out.
+pub fn agenda(notes: &[Note], today: NaiveDate, highlight: bool) -> Vec<String> {
+    let mut open: Vec<(&Note, NaiveDate)> = notes
+        .iter()
+        .filter(|n| n.todo.is_some_and(|(_, done)| !done))
+        .filter_map(|n| n.due.map(|due| (n, due)))
+        .collect();
+    open.sort_by_key(|(n, due)| (*due, ::std::cmp::Reverse(n.todo.map(|(p, _)| p))));
+    let end_of_week = today + Duration::days(AGENDA_DAYS);
+    let sections: [(&str, &dyn Fn(NaiveDate) -> bool); 3] = [
+        ("Overdue", &|due| due < today),
+        ("Today", &|due| due == today),
+        ("This week", &|due| due > today && due < end_of_week),
+    ];
+    let mut lines = Vec::new();
+    for (heading, in_section) in sections.iter() {
+        let items: Vec<String> = open
+            .iter()
+            .filter(|(_, due)| in_section(*due))
+            .filter_map(|(n, _)| todo_line(n, today, highlight))
+            .collect();
+        if !items.is_empty() {
+            lines.push(format!("{}:", heading));
+            lines.extend(items.into_iter().map(|item| format!("  {}", item)));
+        }
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_due_dates() {
+        // A Saturday.
+        let today = NaiveDate::from_ymd_opt(2026, 10, 17).unwrap();
+        let date = |y, m, d| Ok(NaiveDate::from_ymd_opt(y, m, d).unwrap());
+        assert_eq!(parse_due("2026-12-25", today), date(2026, 12, 25));
+        assert_eq!(parse_due("Tomorrow", today), date(2026, 10, 18));
+        assert_eq!(parse_due("in 3 days", today), date(2026, 10, 20));
+        assert_eq!(parse_due("in 2 weeks", today), date(2026, 10, 31));
+        assert_eq!(parse_due("fri", today), date(2026, 10, 23));
+        assert_eq!(parse_due("saturday", today), date(2026, 10, 24));
+        assert!(parse_due("someday", today).is_err());
+        assert!(parse_due("in three days", today).is_err());
+        assert!(parse_due("in 999999999999 days", today).is_err());
+        assert!(parse_due("in 99999999 weeks", today).is_err());
+    }
+
+    #[test]
+    fn agenda_sections() {
+        let today = NaiveDate::from_ymd_opt(2026, 10, 17).unwrap();
+        let item = |content: &str, days: i64, done: bool| {
+            let mut note = Note::new(String::from(content));
+            note.todo = Some((3, done));
+            note.due = Some(today + Duration::days(days));
+            note
+        };
+        let notes = vec![
+            item("Later", 9, false),
+            item("Soon", 2, false),
+            item("Late", -1, false),
+            item("Finished", -1, true),
+            item("Now", 0, false),
+        ];
+        let lines = agenda(&notes, today, false);
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[0], "Overdue:");
+        assert!(lines[1].contains("Late") && lines[1].ends_with("overdue since Fri 2026-10-16"));
+        assert_eq!(lines[2], "Today:");
+        assert_eq!(lines[4], "This week:");
+        assert!(lines[5].contains("Soon"));
+    }
+}
@@ -0,0 +1,167 @@
+//! This module contains functions for editing existing notes.
+
+use std::error::Error;
+use std::path::Path;
+
+use chrono::{self, NaiveDate};
+use clap;
+
+use due::{parse_due, today};
+use global::resolve_tacked_dir;
+use links::warn_dangling;
+use note::{get_content_from_editor, parse_priority, resolve_on};
+use recur::Recurrence;
+use store::{lock_notes, open_store};
+use tags::{add_tags, extract_hashtags};
+
+/// Changes to be made to a note. Fields that are `None` are left unchanged.
+#[derive(Default)]
+pub struct NoteEdit<'a> {
+    pub content: Option<String>,
+    /// `Some(None)` detaches the note from any file.
+    pub on: Option<Option<&'a str>>,
+    /// `Some(None)` turns a to do item back into a plain note.
+    pub priority: Option<Option<i8>>,
+    /// `Some(None)` removes the due date.
+    pub due: Option<Option<NaiveDate>>,
+    /// `Some(None)` stops the to do item repeating.
+    pub recur: Option<Option<Recurrence>>,
+}
+
+/// Main entry point to the `edit` subcommand.
+///
+/// If no new content is given with `-m` and no other changes are requested,
+/// the current content of the note is opened in `$EDITOR`.
+pub fn run_edit(input: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let tacked_dir = resolve_tacked_dir(input)?;
+    let id = input.value_of("id").expect("ID is a required argument.");
+    let mut edit = NoteEdit::default();
+    if input.is_present("detach") {
+        edit.on = Some(None);
+    } else if let Some(on) = input.value_of("on") {
+        edit.on = Some(Some(on));
+    }
+    if input.is_present("no_todo") {
+        edit.priority = Some(None);
+    } else if let Some(priority) = input.value_of("priority") {
+        edit.priority = Some(Some(parse_priority(priority)?));
+    }
+    if input.is_present("no_due") {
+        edit.due = Some(None);
+    } else if let Some(due) = input.value_of("due") {
+        edit.due = Some(Some(parse_due(due, today())?));
+    }
+    if input.is_present("no_recur") {
+        edit.recur = Some(None);
+    } else if let Some(recur) = input.value_of("recur") {
+        edit.recur = Some(Some(recur.parse()?));
+    }
+    if let Some(content) = input.value_of("note") {
+        edit.content = Some(String::from(content));
+    } else if edit.on.is_none()
+        && edit.priority.is_none()
+        && edit.due.is_none()
+        && edit.recur.is_none()
+    {
+        let note = open_store(&tacked_dir)?.find(id)?;
+        edit.content = Some(get_content_from_editor(&note.content)?);
+    }
+    if let Some(ref content) = edit.content {
+        if content.split_whitespace().next().is_none() {
+            return Err(From::from("Note has no content. Aborting."));
+        }
+    }
+    let content = edit.content.clone();
+    edit_note(id, edit, &tacked_dir)?;
+    println!("Edited note.");
+    if let Some(content) = content {
+        warn_dangling(&content, &tacked_dir)?;
+    }
+
+    Ok(())
+}
+
+/// Applies changes to a note given a partial ID.
+///
+/// The original author and creation time are kept and the `modified` time is
+/// updated.
+pub fn edit_note(id: &str, edit: NoteEdit, tacked_dir: &Path) -> Result<(), Box<dyn Error>> {
+    let _lock = lock_notes(tacked_dir)?;
+    let mut store = open_store(tacked_dir)?;
+    let mut note = store.find(id)?;
+    let maybe_on = match edit.on {
+        Some(on) => Some(resolve_on(on, tacked_dir)?),
+        None => None,
+    };
+    if let Some(content) = edit.content {
+        let hashtags = extract_hashtags(&content);
+        add_tags(&mut note, hashtags);
+        note.content = content;
+    }
+    if let Some((on, anchor)) = maybe_on {
+        note.on = on;
+        note.anchor = anchor;
+    }
+    match edit.priority {
+        Some(Some(priority)) => {
+            let complete = note.todo.is_some_and(|(_, c)| c);
+            note.todo = Some((priority, complete));
+        }
+        Some(None) => {
+            note.todo = None;
+            note.completed = None;
+            note.due = None;
+            note.recur = None;
+        }
+        None => (),
+    }
+    if let Some(due) = edit.due {
+        if due.is_some() && note.todo.is_none() {
+            note.todo = Some((3, false));
+        }
+        note.due = due;
+    }
+    if let Some(recur) = edit.recur {
+        if recur.is_some() && note.todo.is_none() {
+            note.todo = Some((3, false));
+        }
+        note.recur = recur;
+    }
+    note.modified = Some(chrono::Local::now());
+    store.update(&note)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use note::{create_note, get_notes};
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn edit_keeps_metadata() {
+        let temp_dir = TempDir::new("edit_test").expect("Could not create temp directory.");
+        let tacked_path = temp_dir.path().join(".tacked");
+        fs::create_dir(tacked_path.clone()).unwrap();
+        let content = String::from("This is a tset note.");
+        create_note(content, None, None, &[], &tacked_path).unwrap();
+        let before = get_notes(&tacked_path).unwrap();
+        let edit = NoteEdit {
+            content: Some(String::from("This is a test note.")),
+            priority: Some(Some(4)),
+            due: Some(NaiveDate::from_ymd_opt(2026, 10, 23)),
+            ..Default::default()
+        };
+        edit_note(&before[0].id, edit, &tacked_path).unwrap();
+        let after = get_notes(&tacked_path).unwrap();
+        assert_eq!(after[0].id, before[0].id);
+        assert_eq!(after[0].datetime, before[0].datetime);
+        assert_eq!(after[0].user, before[0].user);
+        assert_eq!(after[0].content, "This is a test note.");
+        assert_eq!(after[0].todo, Some((4, false)));
+        assert_eq!(after[0].due, NaiveDate::from_ymd_opt(2026, 10, 23));
+        assert!(after[0].modified.is_some());
+    }
+}
@@ -0,0 +1,276 @@
+//! This module contains the structured output formats of `show`, for use by
+//! scripts and for publishing notes.
+//!
+//! Every format uses the same field names, which are kept stable between
+//! versions even if the way notes are stored changes.
+
+use std::str::FromStr;
+
+use serde_json::{self, Value};
+
+use note::Note;
+
+/// The fields of a note in structured output, in the order used by CSV. New
+/// fields go at the end, before `source`, so that columns keep their places.
+pub const FIELDS: [&str; 18] = [
+    "id",
+    "author",
+    "created",
+    "modified",
+    "completed",
+    "todo",
+    "priority",
+    "done",
+    "on",
+    "start_line",
+    "end_line",
+    "tags",
+    "content",
+    "due",
+    "recur",
+    "parent",
+    "email",
+    "source",
+];
+
+/// How `show` prints notes.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OutputFormat {
+    /// Human readable text.
+    #[default]
+    Text,
+    Structured(StructuredFormat),
+}
+
+/// The formats of `show` for scripts and publishing, which all have the same
+/// fields.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StructuredFormat {
+    /// A JSON array of notes.
+    Json,
+    /// One JSON object per line.
+    Jsonl,
+    Csv,
+    Markdown,
+    /// A complete HTML page.
+    Html,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<OutputFormat, String> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Structured(StructuredFormat::Json)),
+            "jsonl" => Ok(OutputFormat::Structured(StructuredFormat::Jsonl)),
+            "csv" => Ok(OutputFormat::Structured(StructuredFormat::Csv)),
+            "markdown" => Ok(OutputFormat::Structured(StructuredFormat::Markdown)),
+            "html" => Ok(OutputFormat::Structured(StructuredFormat::Html)),
+            _ => Err(format!(
+                "Unknown format '{}', expected text, json, jsonl, csv, markdown or html.",
+                s
+            )),
+        }
+    }
+}
+
+/// A note as it appears in structured output.
+#[derive(Serialize)]
+struct NoteRecord<'a> {
+    id: &'a str,
+    author: Option<&'a str>,
+    created: String,
+    modified: Option<String>,
+    completed: Option<String>,
+    todo: bool,
+    priority: Option<i8>,
+    done: Option<bool>,
+    on: Option<String>,
+    start_line: Option<usize>,
+    end_line: Option<usize>,
+    tags: &'a [String],
+    content: &'a str,
+    due: Option<String>,
+    recur: Option<String>,
+    parent: Option<&'a str>,
+    email: Option<&'a str>,
+    /// The store the note came from when showing several, e.g. `global`.
+    source: Option<&'a str>,
+}
+
+impl<'a> NoteRecord<'a> {
+    fn new(source: &'a str, note: &'a Note) -> NoteRecord<'a> {
+        NoteRecord {
+            id: &note.id,
+            author: note.user.as_deref(),
+            created: note.datetime.to_rfc3339(),
+            modified: note.modified.map(|m| m.to_rfc3339()),
+            completed: note.completed.map(|c| c.to_rfc3339()),
+            todo: note.todo.is_some(),
+            priority: note.todo.map(|(p, _)| p),
+            done: note.todo.map(|(_, d)| d),
+            on: note.on.as_ref().map(|on| on.to_string_lossy().into_owned()),
+            start_line: note.anchor.as_ref().map(|a| a.start),
+            end_line: note.anchor.as_ref().map(|a| a.end),
+            tags: &note.tags,
+            content: &note.content,
+            due: note.due.map(|d| d.to_string()),
+            recur: note.recur.map(|r| r.to_string()),
+            parent: note.parent.as_deref(),
+            email: note.email.as_deref(),
+            source: if source.is_empty() {
+                None
+            } else {
+                Some(source)
+            },
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        serde_json::to_value(self).expect("Note records can always be serialised.")
+    }
+}
+
+/// Formats notes, each paired with the name of the store it came from, as
+/// lines of output.
+pub fn format_notes(notes: &[(&str, Note)], format: StructuredFormat) -> Vec<String> {
+    let records: Vec<NoteRecord> = notes
+        .iter()
+        .map(|(source, note)| NoteRecord::new(source, note))
+        .collect();
+    let values = || records.iter().map(NoteRecord::to_value);
+    match format {
+        StructuredFormat::Json => {
+            vec![serde_json::to_string_pretty(&records)
+                .expect("Note records can always be serialised.")]
+        }
+        StructuredFormat::Jsonl => records
+            .iter()
+            .map(|r| serde_json::to_string(r).expect("Note records can always be serialised."))
+            .collect(),
+        StructuredFormat::Csv => format_csv(&values().collect::<Vec<Value>>()),
+        StructuredFormat::Markdown => values().map(|v| format_markdown(&v)).collect(),
+        StructuredFormat::Html => format_html(&values().collect::<Vec<Value>>()),
+    }
+}
+
+/// Converts a field to plain text, with tags separated by `;`.
+fn field_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items
+            .iter()
+            .map(field_text)
+            .collect::<Vec<String>>()
+            .join(";"),
+        other => other.to_string(),
+    }
+}
+
+fn format_csv(records: &[Value]) -> Vec<String> {
+    let mut lines = vec![FIELDS.join(",")];
+    for record in records.iter() {
+        let row: Vec<String> = FIELDS
+            .iter()
+            .map(|field| {
+                let text = field_text(&record[*field]);
+                if text.contains([',', '"', '\n', '\r']) {
+                    format!("\"{}\"", text.replace('"', "\"\""))
+                } else {
+                    text
+                }
+            })
+            .collect();
+        lines.push(row.join(","));
+    }
+
+    lines
+}
+
+fn format_markdown(record: &Value) -> String {
+    let mut section = format!("## {}\n\n", field_text(&record["id"]));
+    for field in FIELDS.iter().filter(|f| **f != "id" && **f != "content") {
+        let text = field_text(&record[*field]);
+        if !text.is_empty() {
+            section.push_str(&format!("- **{}:** {}\n", field, text));
+        }
+    }
+    section.push_str(&format!("\n{}\n", field_text(&record["content"])));
+
+    section
+}
+
+fn format_html(records: &[Value]) -> Vec<String> {
+    let mut lines = vec![
+        String::from("<!DOCTYPE html>"),
+        String::from("<html>"),
+        String::from("<head><meta charset=\"utf-8\"><title>Notes</title></head>"),
+        String::from("<body>"),
+        String::from("<h1>Notes</h1>"),
+    ];
+    for record in records.iter() {
+        let id = escape_html(&field_text(&record["id"]));
+        lines.push(format!("<article id=\"note-{}\">", id));
+        lines.push(format!("<h2>{}</h2>", id));
+        lines.push(String::from("<dl>"));
+        for field in FIELDS.iter().filter(|f| **f != "id" && **f != "content") {
+            let text = field_text(&record[*field]);
+            if !text.is_empty() {
+                lines.push(format!("<dt>{}</dt><dd>{}</dd>", field, escape_html(&text)));
+            }
+        }
+        lines.push(String::from("</dl>"));
+        lines.push(format!(
+            "<pre class=\"content\">{}</pre>",
+            escape_html(&field_text(&record["content"]))
+        ));
+        lines.push(String::from("</article>"));
+    }
+    lines.push(String::from("</body>"));
+    lines.push(String::from("</html>"));
+
+    lines
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use note::{create_note, get_notes};
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn structured_formats() {
+        let temp_dir = TempDir::new("format_test").expect("Could not create temp directory.");
+        let tacked_path = temp_dir.path().join(".tacked");
+        fs::create_dir(tacked_path.clone()).unwrap();
+        let content = String::from("Fix <this>, \"quickly\".");
+        create_note(content, None, Some((4, false)), &[], &tacked_path).unwrap();
+        let note = get_notes(&tacked_path).unwrap().pop().unwrap();
+        let notes = vec![("", note.clone())];
+        let json: Value =
+            serde_json::from_str(&format_notes(&notes, StructuredFormat::Json)[0]).unwrap();
+        for field in FIELDS.iter() {
+            assert!(json[0].get(*field).is_some(), "Missing field {}.", field);
+        }
+        assert_eq!(json[0]["id"], note.id);
+        assert_eq!(json[0]["priority"], 4);
+        assert_eq!(json[0]["done"], false);
+        let csv = format_notes(&notes, StructuredFormat::Csv);
+        assert!(csv[0].starts_with("id,author,created,"));
+        assert!(csv[0].ends_with(",content,due,recur,parent,email,source"));
+        assert!(csv[1].contains(",\"Fix <this>, \"\"quickly\"\".\","));
+        let html = format_notes(&notes, StructuredFormat::Html).join("\n");
+        assert!(html.contains("Fix &lt;this&gt;, &quot;quickly&quot;."));
+        assert_eq!(format_notes(&notes, StructuredFormat::Jsonl).len(), 1);
+    }
+}
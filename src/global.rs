@@ -0,0 +1,48 @@
+//! This module contains functions for finding the global notes, which are kept
+//! in the user's data directory rather than in a project.
+
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap;
+use dirs;
+
+use init::find_tacked_notes;
+
+/// The path of the global notes directory, e.g. `~/.local/share/tack-it-on` on
+/// Linux.
+///
+/// This is deliberately not `~/.tacked`, which would be found as a project by
+/// any `tack` command run beneath the home directory.
+pub fn global_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|d| d.join("tack-it-on"))
+}
+
+/// Gets the global notes directory, creating it if needed.
+pub fn global_tacked_dir() -> Result<PathBuf, Box<dyn Error>> {
+    let global_dir = global_path().ok_or("Could not find a data directory for global notes.")?;
+    fs::create_dir_all(&global_dir)?;
+
+    Ok(global_dir)
+}
+
+/// Whether a notes directory is the global notes directory.
+pub fn is_global(tacked_dir: &Path) -> bool {
+    global_path().is_some_and(|g| g == tacked_dir)
+}
+
+/// Finds the notes directory a subcommand should use.
+///
+/// This is the global notes directory if `--global` is given or no project is
+/// found, otherwise it is the project's `.tacked` directory.
+pub fn resolve_tacked_dir(input: &clap::ArgMatches) -> Result<PathBuf, Box<dyn Error>> {
+    if input.is_present("global") {
+        return global_tacked_dir();
+    }
+    let cwd = Path::new(".").canonicalize()?;
+    match find_tacked_notes(&cwd)? {
+        Some(tacked_dir) => Ok(tacked_dir),
+        None => global_tacked_dir(),
+    }
+}
@@ -0,0 +1,193 @@
+//! Export of to do items as an [iCalendar] file, for calendar and task apps.
+//!
+//! Each to do item becomes a `VTODO`, or an all-day `VEVENT` on its due date
+//! for calendars that don't show to do items. UIDs are made from the note ID,
+//! so importing a newer export updates the entries rather than adding them
+//! again.
+//!
+//! [iCalendar]: https://datatracker.ietf.org/doc/html/rfc5545
+
+use std::path::Path;
+
+use chrono::{self, Local, Utc};
+
+use note::Note;
+
+/// iCalendar's format for times, in UTC.
+const TIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// Lines longer than this many bytes are folded onto the next line.
+const MAX_LINE: usize = 75;
+
+fn format_time(time: &chrono::DateTime<Local>) -> String {
+    time.with_timezone(&Utc).format(TIME_FORMAT).to_string()
+}
+
+/// Escapes commas, semicolons, backslashes and new lines in text values.
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Folds a content line so that no line is longer than `MAX_LINE` bytes,
+/// without splitting characters. Continuation lines start with a space.
+fn fold_line(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > MAX_LINE {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+
+    folded
+}
+
+/// Maps a priority, where higher is more important, onto iCalendar's 1
+/// (highest) to 9 (lowest). The default priority of 3 is 5, medium.
+fn ical_priority(priority: i8) -> i16 {
+    (8 - priority as i16).clamp(1, 9)
+}
+
+/// A `file://` URL for a path, with characters that aren't allowed in URLs
+/// percent-encoded.
+fn file_url(path: &Path) -> String {
+    let mut url = String::from("file://");
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                url.push(byte as char)
+            }
+            _ => url.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    url
+}
+
+/// Writes the to do items among the notes as an iCalendar file. The paths
+/// notes are on are made absolute from `project_dir`.
+///
+/// If `events` is set, dated items are written as all-day events and items
+/// without a due date are left out.
+pub fn to_ical(notes: &[Note], project_dir: &Path, events: bool) -> String {
+    let mut lines: Vec<String> = vec![
+        String::from("BEGIN:VCALENDAR"),
+        String::from("VERSION:2.0"),
+        String::from("PRODID:-//tack-it-on//tack-it-on//EN"),
+        String::from("CALSCALE:GREGORIAN"),
+    ];
+    for note in notes.iter() {
+        let (priority, done) = match note.todo {
+            Some(todo) => todo,
+            None => continue,
+        };
+        if events && note.due.is_none() {
+            continue;
+        }
+        let component = if events { "VEVENT" } else { "VTODO" };
+        lines.push(format!("BEGIN:{}", component));
+        lines.push(format!("UID:{}@tack-it-on", note.id));
+        lines.push(format!(
+            "DTSTAMP:{}",
+            format_time(note.modified.as_ref().unwrap_or(&note.datetime))
+        ));
+        lines.push(format!("CREATED:{}", format_time(&note.datetime)));
+        if let Some(ref modified) = note.modified {
+            lines.push(format!("LAST-MODIFIED:{}", format_time(modified)));
+        }
+        let summary = note.content.lines().next().unwrap_or("");
+        lines.push(format!("SUMMARY:{}", escape_text(summary.trim())));
+        lines.push(format!("DESCRIPTION:{}", escape_text(&note.content)));
+        lines.push(format!("PRIORITY:{}", ical_priority(priority)));
+        if !note.tags.is_empty() {
+            let tags: Vec<String> = note.tags.iter().map(|t| escape_text(t)).collect();
+            lines.push(format!("CATEGORIES:{}", tags.join(",")));
+        }
+        if let Some(ref on) = note.on {
+            lines.push(format!("URL:{}", file_url(&project_dir.join(on))));
+        }
+        match (note.due, events) {
+            (Some(due), true) => {
+                lines.push(format!("DTSTART;VALUE=DATE:{}", due.format("%Y%m%d")));
+                let next_day = due.succ_opt().unwrap_or(due);
+                lines.push(format!("DTEND;VALUE=DATE:{}", next_day.format("%Y%m%d")));
+                lines.push(String::from("TRANSP:TRANSPARENT"));
+            }
+            (Some(due), false) => {
+                // Recurrences are counted from DTSTART, which calendars expect
+                // alongside RRULE.
+                if note.recur.is_some() {
+                    lines.push(format!("DTSTART;VALUE=DATE:{}", due.format("%Y%m%d")));
+                }
+                lines.push(format!("DUE;VALUE=DATE:{}", due.format("%Y%m%d")));
+            }
+            (None, _) => (),
+        }
+        // Without a date there is nothing for the recurrence to start from.
+        if let (Some(ref recur), Some(_)) = (note.recur, note.due) {
+            lines.push(format!("RRULE:{}", recur.to_rrule()));
+        }
+        if !events {
+            if done {
+                lines.push(String::from("STATUS:COMPLETED"));
+                lines.push(String::from("PERCENT-COMPLETE:100"));
+                if let Some(ref completed) = note.completed {
+                    lines.push(format!("COMPLETED:{}", format_time(completed)));
+                }
+            } else {
+                lines.push(String::from("STATUS:NEEDS-ACTION"));
+            }
+        }
+        lines.push(format!("END:{}", component));
+    }
+    lines.push(String::from("END:VCALENDAR"));
+
+    lines.iter().map(|line| fold_line(line)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use std::path::PathBuf;
+
+    #[test]
+    fn ical_to_do_items() {
+        let mut item = Note::new(String::from("Fix the parser, quickly.\nIt's slow."));
+        item.todo = Some((5, false));
+        item.due = NaiveDate::from_ymd_opt(2026, 10, 23);
+        item.on = Some(PathBuf::from("src/my parser.rs"));
+        item.tags = vec![String::from("bug")];
+        item.recur = Some("monthly".parse().unwrap());
+        let mut undated = Note::new("A".repeat(100));
+        undated.todo = Some((3, true));
+        undated.recur = Some("weekly".parse().unwrap());
+        let plain = Note::new(String::from("Not a to do item."));
+        let notes = vec![item.clone(), undated, plain];
+        let ical = to_ical(&notes, Path::new("/project"), false);
+        assert!(ical.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ical.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(ical.matches("BEGIN:VTODO").count(), 2);
+        assert!(ical.contains(&format!("UID:{}@tack-it-on\r\n", item.id)));
+        assert!(ical.contains("SUMMARY:Fix the parser\\, quickly.\r\n"));
+        assert!(ical.contains("DESCRIPTION:Fix the parser\\, quickly.\\nIt's slow.\r\n"));
+        assert!(ical.contains("PRIORITY:3\r\n"));
+        assert!(ical.contains("DTSTART;VALUE=DATE:20261023\r\nDUE;VALUE=DATE:20261023\r\n"));
+        assert!(ical.contains("RRULE:FREQ=MONTHLY;INTERVAL=1\r\n"));
+        assert_eq!(ical.matches("RRULE:").count(), 1);
+        assert!(ical.contains("URL:file:///project/src/my%20parser.rs\r\n"));
+        assert!(ical.contains("STATUS:COMPLETED\r\n"));
+        assert!(ical.split("\r\n").all(|line| line.len() <= MAX_LINE));
+        let events = to_ical(&notes, Path::new("/project"), true);
+        assert_eq!(events.matches("BEGIN:VEVENT").count(), 1);
+        assert!(events.contains("DTEND;VALUE=DATE:20261024\r\n"));
+    }
+}
@@ -6,15 +6,19 @@ use std::error::Error;
 use std::fs;
 use std::path::{Path, PathBuf};
 
+use clap;
 use glob::glob;
 
+use config::{save_config, Config};
+use store::{ignore_local_files, StoreKind};
+
 /// Crawls up file tree to root looking for a `.tacked` directory.
 /// Initialises tack-it-on in the current directory.
 ///
 /// If a `.tacked` directory is found in a parent directory, the user will be
 /// asked if they wish to uses that directory to store notes or create a new
 /// one.
-pub fn run_init() -> Result<(), Box<Error>> {
+pub fn run_init(input: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
     let cwd = Path::new(".").canonicalize()?;
     println!("Tacking notes onto {:?}...", cwd);
     let parent_tacked = find_tacked_notes(&cwd)?;
@@ -23,7 +27,17 @@ pub fn run_init() -> Result<(), Box<Error>> {
         continue_init = query_init(&cwd, &dir)?;
     }
     if continue_init {
+        let store: StoreKind = input.value_of("store").unwrap_or("json").parse()?;
         create_tacked(&cwd)?;
+        let config = Config {
+            store,
+            ..Default::default()
+        };
+        let tacked_dir = cwd.join(".tacked");
+        save_config(&config, &tacked_dir)?;
+        if store == StoreKind::Files {
+            ignore_local_files(&tacked_dir)?;
+        }
         println!("Created `.tacked` in {:?}.", cwd);
     } else {
         println!("Did not initialise tacked notes.");
@@ -34,7 +48,7 @@ pub fn run_init() -> Result<(), Box<Error>> {
 
 /// Finds a `.tacked` directory if one is in the path supplied or any of its parent
 /// directories.
-pub fn find_tacked_notes(dir: &PathBuf) -> Result<Option<PathBuf>, Box<Error>> {
+pub fn find_tacked_notes(dir: &Path) -> Result<Option<PathBuf>, Box<dyn Error>> {
     let path_chain = paths_from_crawl(dir);
     for path in path_chain.iter() {
         let found_notes = contains_notes(path);
@@ -50,7 +64,7 @@ pub fn find_tacked_notes(dir: &PathBuf) -> Result<Option<PathBuf>, Box<Error>> {
 ///
 /// The vector of directories will be returned with the uppermost directory
 /// first and the root directory last.
-fn paths_from_crawl(dir: &PathBuf) -> Vec<PathBuf> {
+fn paths_from_crawl(dir: &Path) -> Vec<PathBuf> {
     let mut comp_path = PathBuf::new();
     let mut path_chain: Vec<PathBuf> = Vec::new();
     for component in dir.components() {
@@ -64,54 +78,45 @@ fn paths_from_crawl(dir: &PathBuf) -> Vec<PathBuf> {
 
 /// If the directory contains a `.tacked` directory, Some(PathBuf) is returned
 /// containing the path to the `.tacked` directory.
-fn contains_notes(dir: &PathBuf) -> Option<PathBuf> {
+fn contains_notes(dir: &Path) -> Option<PathBuf> {
     let glob_str = format!("{}/*", dir.to_str().unwrap());
-    for entry in glob(&glob_str).expect("Failed to read glob pattern.") {
-        if let Ok(path) = entry {
-            if path.ends_with(".tacked") {
-                return Some(path);
-            }
-        }
-    }
-
-    None
+    glob(&glob_str)
+        .expect("Failed to read glob pattern.")
+        .flatten()
+        .find(|path| path.ends_with(".tacked"))
 }
 
 /// Queries if initialisation of project should continue.
-fn query_init(cwd: &PathBuf, tacked_loc: &PathBuf) -> Result<bool, String> {
+fn query_init(cwd: &Path, tacked_loc: &Path) -> Result<bool, String> {
     if cwd == tacked_loc {
         return Err(String::from(
             "Current directory already has notes tacked on.",
         ));
     }
     println!("Found tacked notes in parent directory {:?}", tacked_loc);
-    println!(
-        "Do you want to start a new project in {:?} anyway? y/n",
+
+    Ok(ask_yes_no(&format!(
+        "Do you want to start a new project in {:?} anyway?",
         cwd
-    );
-    let mut response: String;
-    let mut opt_init: Option<bool>;
+    )))
+}
+
+/// Asks the user a yes or no question, repeating it until they answer.
+pub fn ask_yes_no(question: &str) -> bool {
+    println!("{} y/n", question);
     loop {
-        response = read!("{}\n");
-        opt_init = match &*response {
-            "y" | "yes" => Some(true),
-            "n" | "no" => Some(false),
-            _ => None,
-        };
-        if let Some(_) = opt_init {
-            break;
+        let response: String = read!("{}\n");
+        match &*response.trim().to_lowercase() {
+            "y" | "yes" => return true,
+            "n" | "no" => return false,
+            _ => (),
         }
     }
-
-    Ok(opt_init.unwrap())
 }
 
 /// Creates a `.tacked` directory in the directory supplied.
-fn create_tacked(cwd: &PathBuf) -> Result<(), std::io::Error> {
-    let mut tacked_path = cwd.clone();
-    tacked_path.push(".tacked");
-
-    fs::create_dir(tacked_path)
+fn create_tacked(cwd: &Path) -> Result<(), std::io::Error> {
+    fs::create_dir(cwd.join(".tacked"))
 }
 
 #[cfg(test)]
@@ -122,7 +127,7 @@ mod tests {
     #[test]
     fn initialize_tackiton() {
         let temp_dir = TempDir::new("init_test").expect("Could not create temp directory.");
-        create_tacked(&temp_dir.path().to_owned()).unwrap();
+        create_tacked(temp_dir.path()).unwrap();
         let tacked_path = temp_dir.path().join(".tacked");
         assert!(tacked_path.exists());
     }
@@ -132,10 +137,10 @@ mod tests {
         let temp_dir = TempDir::new("check_test").expect("Could not create temp directory.");
         let tacked_path = temp_dir.path().join(".tacked");
         fs::create_dir(tacked_path.clone()).unwrap();
-        assert!(contains_notes(&temp_dir.path().to_owned()).is_some());
+        assert!(contains_notes(temp_dir.path()).is_some());
         let not_tacked_path = temp_dir.path().join("tacked");
         fs::create_dir(not_tacked_path.clone()).unwrap();
-        assert!(!contains_notes(&not_tacked_path).is_some());
+        assert!(contains_notes(&not_tacked_path).is_none());
     }
 
     #[test]
@@ -8,56 +8,317 @@ extern crate serde_derive;
 extern crate text_io;
 
 extern crate chrono;
+extern crate dirs;
+extern crate fs2;
 extern crate glob;
+extern crate regex;
+extern crate rusqlite;
 extern crate serde;
 extern crate serde_json;
 extern crate subprocess;
 extern crate tempdir;
 extern crate tempfile;
+extern crate uuid;
+extern crate walkdir;
 
 use std::error::Error;
 
+mod anchor;
+mod author;
+mod config;
+mod doctor;
+mod due;
+mod edit;
+mod format;
+mod global;
+mod ical;
 mod init;
+mod links;
 mod note;
+mod project;
+mod query;
+mod recur;
 mod rm;
+mod scan;
+mod search;
 mod show;
+mod store;
+mod tags;
+mod taskwarrior;
+mod thread;
+mod todo;
+mod todotxt;
+mod transfer;
+mod tree;
 
 /// Processes arguments and runs subcommands.
-pub fn run() -> Result<(), Box<Error>> {
+pub fn run() -> Result<(), Box<dyn Error>> {
     let cli_app = clap_app!(myapp =>
         (version: "0.1.0")
         (author: "Chris Wells Wood <cwwoodesq@gmail.com>")
         (about: "A project centric note-taking application.")
         (@subcommand init =>
             (about: "Initialises a tacked on notes directory.")
+                (@arg store: -s --store +takes_value
+                 possible_values(&["json", "sqlite", "files"])
+                 "How notes are stored: a single json file, an sqlite \
+                  database or one file per note, which merges well under \
+                  version control. Defaults to json.")
         )
         (@subcommand note =>
             (about: "Creates a new note.")
                 (@arg note: -m +takes_value "Note content, wrapped in \"\".")
-                (@arg on: -o --on +takes_value "Tack note onto file.")
+                (@arg on: -o --on +takes_value "Tack note onto file, or onto lines \
+                                                 of it with `file:40-55`.")
                 (@arg todo: -t --todo "Sets note as a to do item. You can set \
                                        a priority with `-p`. Default priority \
                                        is 3")
                 (@arg priority: -p --priority +takes_value
                  "Sets priority of to do item.")
+                (@arg due: -d --due +takes_value
+                 "Sets when the to do item is due, making the note a to do \
+                  item. Takes a date (2026-10-23), today, tomorrow, \
+                  'in 3 days', 'in 2 weeks' or a weekday such as fri.")
+                (@arg recur: -r --recur +takes_value
+                 "Makes the to do item repeat, e.g. monthly, 'every 3 days' or \
+                  FREQ=WEEKLY;INTERVAL=2. Marking it done adds the next one.")
+                (@arg tag: --tag +takes_value +multiple number_of_values(1)
+                 "Tags the note. Can be used more than once. #hashtags in the \
+                  note content are also added as tags.")
+                (@arg global: -g --global "Adds a global note, which isn't part of \
+                                           any project. Notes are also global if \
+                                           no project is found.")
         )
         (@subcommand show =>
             (about: "Show note.")
-                (@arg on: -o --on +takes_value "Show notes on file.")
+                (@arg filter: "Only shows notes matching a filter, e.g. \
+                               'tag:bug and priority>=4 and author:alice and \
+                               created>2026-01-01 and on:src/**'. Fields are \
+                               tag, priority, author, created, modified, due, \
+                               on, id, is (todo, open or done) and text.")
+                (@arg on: -o --on +takes_value "Shows notes on a file, or on \
+                                                 anything in a directory. Glob \
+                                                 patterns such as 'src/**/*.rs' \
+                                                 can be used.")
+                (@arg exact: --exact requires[on] "Only shows notes on exactly \
+                                                   the path given to `--on`.")
                 (@arg oneline: -l --oneline "Prints concise version of the note.")
                 (@arg todo: -t --todo "Shows to do list.")
+                (@arg sort: -s --sort +takes_value requires[todo]
+                 possible_values(&["priority", "due"])
+                 "Sorts the to do list by priority (the default) or by due \
+                  date.")
+                (@arg due_by: --("due-by") +takes_value
+                 "Only shows to do items due on or before a date, e.g. fri.")
+                (@arg overdue: --overdue "Only shows open to do items that are \
+                                          past their due date.")
+                (@arg format: -f --format +takes_value conflicts_with[oneline tree]
+                 possible_values(&["text", "json", "jsonl", "csv", "markdown", "html"])
+                 "Prints notes as text (the default), json, jsonl, csv, \
+                  markdown or an html page.")
+                (@arg tree: --tree conflicts_with[todo oneline]
+                 "Shows notes in a tree of the files and directories they're \
+                  on, with the number of notes in each directory.")
+                (@arg tag: --tag +takes_value +multiple number_of_values(1)
+                 "Shows notes with all of these tags.")
+                (@arg any_tag: --("any-tag") +takes_value +multiple number_of_values(1)
+                 "Shows notes with at least one of these tags.")
+                (@arg not_tag: --("not-tag") +takes_value +multiple number_of_values(1)
+                 "Hides notes with any of these tags.")
+                (@arg author: --author +takes_value conflicts_with[mine]
+                 "Shows notes by an author, given by name or email.")
+                (@arg mine: --mine "Shows your notes, going by your git \
+                                    config.")
+                (@arg global: -g --global conflicts_with[all]
+                 "Shows global notes rather than the project's.")
+                (@arg all: -a --all "Shows project and global notes together.")
+        )
+        (@subcommand search =>
+            (about: "Searches the content, files and tags of notes, showing the \
+                     most relevant first.")
+                (@arg terms: +required +multiple "Words to search for. Notes must \
+                                                  contain all of them.")
+                (@arg regex: -r --regex "Treats the terms as regular expressions, \
+                                         matching notes containing any of them.")
+                (@arg case_sensitive: -c --("case-sensitive") "Matches case exactly.")
+                (@arg oneline: -l --oneline "Prints concise version of the note.")
+                (@arg global: -g --global "Searches the global notes rather than \
+                                           the project's.")
+        )
+        (@subcommand tags =>
+            (about: "Lists all tags with the number of notes using them.")
+                (@arg rename: --rename +takes_value number_of_values(2)
+                 value_names(&["OLD", "NEW"]) conflicts_with[merge]
+                 "Renames a tag on every note, merging it into NEW if that \
+                  tag already exists.")
+                (@arg merge: --merge +takes_value +multiple requires[into]
+                 "Merges these tags into the tag given by `--into`.")
+                (@arg into: --into +takes_value "Tag to merge into.")
+                (@arg global: -g --global "Uses the global notes rather than the project's.")
         )
         (@subcommand rm =>
             (about: "Remove note.")
-                (@arg id: -i --id +takes_value "Removes note with matching ID.")
+                (@arg id: -i --id +takes_value conflicts_with[where]
+                 "Removes note with matching ID.")
+                (@arg where: -w --where +takes_value
+                 "Removes every note matching a filter, as used by `show`.")
+                (@arg cascade: -c --cascade
+                 "Also removes replies to the notes. Otherwise replies are \
+                  moved up to the removed note's parent.")
+                (@arg yes: -y --yes
+                 "Doesn't ask before removing notes matching a filter or \
+                  their replies.")
+                (@arg global: -g --global "Uses the global notes rather than the project's.")
+        )
+        (@subcommand reply =>
+            (about: "Replies to a note. Replies are shown indented under the \
+                     note they answer.")
+                (@arg id: +required "ID of the note to reply to.")
+                (@arg note: -m +takes_value "Reply content, wrapped in \"\".")
+                (@arg tag: --tag +takes_value +multiple number_of_values(1)
+                 "Tags the reply. Can be used more than once.")
+                (@arg global: -g --global "Uses the global notes rather than the project's.")
+        )
+        (@subcommand edit =>
+            (about: "Edits an existing note. Opens the note in $EDITOR unless \
+                     new content or other changes are given.")
+                (@arg id: -i --id +takes_value +required "Edits note with matching ID.")
+                (@arg note: -m +takes_value "New note content, wrapped in \"\".")
+                (@arg on: -o --on +takes_value conflicts_with[detach]
+                 "Tack note onto a different file, or onto lines of it with \
+                  `file:40-55`.")
+                (@arg detach: --detach "Detaches note from its file.")
+                (@arg priority: -p --priority +takes_value conflicts_with[no_todo]
+                 "Sets priority, turning the note into a to do item if needed.")
+                (@arg no_todo: --("no-todo") conflicts_with[due]
+                 "Turns a to do item into a plain note.")
+                (@arg due: -d --due +takes_value conflicts_with[no_due]
+                 "Sets when the to do item is due, turning the note into a to \
+                  do item if needed.")
+                (@arg no_due: --("no-due") "Removes the due date.")
+                (@arg recur: -r --recur +takes_value conflicts_with[no_recur no_todo]
+                 "Makes the to do item repeat, e.g. monthly or 'every 3 days'.")
+                (@arg no_recur: --("no-recur") "Stops the to do item repeating.")
+                (@arg global: -g --global "Uses the global notes rather than the project's.")
+        )
+        (@subcommand agenda =>
+            (about: "Lists the open to do items that are overdue, due today \
+                     and due in the next week.")
+                (@arg global: -g --global "Uses the global notes rather than the project's.")
+        )
+        (@subcommand scan =>
+            (about: "Finds TODO, FIXME and XXX comments in the project's files \
+                     and adds them as to do items on those lines. Comments \
+                     that have already been added are skipped.")
+                (@arg marker: -M --marker +takes_value +multiple number_of_values(1)
+                 "Looks for this word rather than TODO, FIXME and XXX. Can be \
+                  used more than once. The default can be changed with \
+                  `scan_markers` in `.tacked/config.json`.")
+                (@arg remove: --remove "Removes the comments from the files.")
+                (@arg yes: -y --yes requires[remove]
+                 "Doesn't ask before removing comments.")
+        )
+        (@subcommand doctor =>
+            (about: "Finds notes tacked onto files that no longer exist and \
+                     suggests where the files have gone, and links between \
+                     notes that lead nowhere.")
+                (@arg global: -g --global "Uses the global notes rather than the project's.")
+        )
+        (@subcommand relink =>
+            (about: "Moves notes tacked onto missing files to the new locations \
+                     suggested by `doctor`.")
+                (@arg archive: --archive "Also archives notes that can't be \
+                                          relinked by tagging them `archived`.")
+                (@arg yes: -y --yes "Doesn't ask before changing notes.")
+                (@arg global: -g --global "Uses the global notes rather than the project's.")
+        )
+        (@subcommand graph =>
+            (about: "Prints a Graphviz DOT graph of the links between notes, \
+                     the replies to them and the files they're on.")
+                (@arg filter: "Only includes notes matching a filter, as used by `show`.")
+                (@arg no_files: --("no-files") "Leaves out the files notes are on.")
+                (@arg global: -g --global "Uses the global notes rather than the project's.")
+        )
+        (@subcommand export =>
+            (about: "Prints notes in a format that can be imported into another \
+                     project.")
+                (@arg filter: "Only exports notes matching a filter, as used by `show`.")
+                (@arg format: -f --format +takes_value
+                 possible_values(&["json", "todotxt", "taskwarrior", "ics"])
+                 "Exports every note as json (the default), or to do items as \
+                  todo.txt lines, Taskwarrior json or an iCalendar file.")
+                (@arg events: --events
+                 "With `--format ics`, writes to do items with a due date as \
+                  all-day events, for calendars that don't show to do items.")
+                (@arg global: -g --global "Exports the global notes rather than the project's.")
+        )
+        (@subcommand import =>
+            (about: "Imports exported notes, skipping notes that are already \
+                     present and reporting any that have changed.")
+                (@arg file: +required "File of exported notes.")
+                (@arg format: -f --format +takes_value
+                 possible_values(&["json", "todotxt", "taskwarrior"])
+                 "Format of the file: json from `export` (the default), \
+                  todo.txt or Taskwarrior json.")
+                (@arg update: -u --update
+                 "Replaces notes that have changed rather than reporting them.")
+                (@arg prefix: --prefix +takes_value
+                 "Adds this directory to the start of the files notes are on.")
+                (@arg strip_prefix: --("strip-prefix") +takes_value
+                 "Removes this directory from the start of the files notes are \
+                  on, before any `--prefix` is added.")
+                (@arg global: -g --global "Imports into the global notes rather \
+                                           than the project's.")
+        )
+        (@subcommand migrate_store =>
+            (name: "migrate-store")
+            (about: "Moves all notes into a different kind of store.")
+                (@arg to: +required possible_values(&["json", "sqlite", "files"])
+                 "The kind of store to move the notes into.")
+        )
+        (@subcommand done =>
+            (about: "Marks a to do item as done. If it repeats, the next one \
+                     is added with a new due date.")
+                (@arg id: -i --id +takes_value +required
+                 "Completes to do item with matching ID.")
+                (@arg global: -g --global "Uses the global notes rather than the project's.")
+        )
+        (@subcommand reopen =>
+            (about: "Marks a completed to do item as not done.")
+                (@arg id: -i --id +takes_value +required
+                 "Reopens to do item with matching ID.")
+                (@arg global: -g --global "Uses the global notes rather than the project's.")
+        )
+        (@subcommand reprioritise =>
+            (about: "Changes the priority of a to do item.")
+                (@arg id: -i --id +takes_value +required
+                 "Reprioritises to do item with matching ID.")
+                (@arg priority: -p --priority +takes_value +required
+                 "New priority of the to do item.")
+                (@arg global: -g --global "Uses the global notes rather than the project's.")
         )
     ).get_matches();
 
     match cli_app.subcommand() {
-        ("init", _) => init::run_init(),
+        ("init", Some(sub_args)) => init::run_init(sub_args),
         ("note", Some(sub_args)) => note::run_note(sub_args),
         ("show", Some(sub_args)) => show::run_show(sub_args),
+        ("search", Some(sub_args)) => search::run_search(sub_args),
+        ("tags", Some(sub_args)) => tags::run_tags(sub_args),
         ("rm", Some(sub_args)) => rm::run_rm(sub_args),
+        ("reply", Some(sub_args)) => thread::run_reply(sub_args),
+        ("edit", Some(sub_args)) => edit::run_edit(sub_args),
+        ("agenda", Some(sub_args)) => due::run_agenda(sub_args),
+        ("scan", Some(sub_args)) => scan::run_scan(sub_args),
+        ("doctor", Some(sub_args)) => doctor::run_doctor(sub_args),
+        ("relink", Some(sub_args)) => doctor::run_relink(sub_args),
+        ("graph", Some(sub_args)) => links::run_graph(sub_args),
+        ("export", Some(sub_args)) => transfer::run_export(sub_args),
+        ("import", Some(sub_args)) => transfer::run_import(sub_args),
+        ("migrate-store", Some(sub_args)) => store::run_migrate_store(sub_args),
+        ("done", Some(sub_args)) => todo::run_done(sub_args),
+        ("reopen", Some(sub_args)) => todo::run_reopen(sub_args),
+        ("reprioritise", Some(sub_args)) => todo::run_reprioritise(sub_args),
         _ => Err(From::from(cli_app.usage())),
     }
 }
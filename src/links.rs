@@ -0,0 +1,262 @@
+//! This module contains links between notes, written as `[[a1b2c3]]` in a
+//! note's content with the start of another note's ID, and the `graph`
+//! subcommand which draws them.
+
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use clap;
+use regex::Regex;
+
+use global::resolve_tacked_dir;
+use note::{find_note, get_notes, Note};
+use query::Query;
+
+/// The shortest partial ID recognised as a link, so that ordinary text in
+/// double brackets isn't mistaken for one.
+const MIN_LINK_LENGTH: usize = 4;
+
+/// Main entry point for the `graph` subcommand.
+pub fn run_graph(input: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let tacked_dir = resolve_tacked_dir(input)?;
+    let mut notes = get_notes(&tacked_dir)?;
+    if let Some(filter) = input.value_of("filter") {
+        let query = Query::parse(filter)?;
+        notes.retain(|n| query.matches(n));
+    }
+    println!("{}", to_dot(&notes, !input.is_present("no_files")));
+
+    Ok(())
+}
+
+/// The pattern of a link, compiled on first use.
+fn link_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"\[\[([0-9a-fA-F]+)\]\]").expect("Link pattern is valid."))
+}
+
+/// The notes' IDs in order, for finding the note a link leads to without
+/// searching every note.
+pub struct IdIndex<'a> {
+    ids: Vec<(&'a str, usize)>,
+}
+
+impl<'a> IdIndex<'a> {
+    pub fn new(notes: &'a [Note]) -> IdIndex<'a> {
+        let mut ids: Vec<(&str, usize)> = notes
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (n.id.as_str(), i))
+            .collect();
+        ids.sort_unstable();
+        IdIndex { ids }
+    }
+
+    /// The index of the one note whose ID starts with a link, if exactly one
+    /// does.
+    pub fn resolve(&self, link: &str) -> Option<usize> {
+        let start = self.ids.partition_point(|(id, _)| *id < link);
+        let mut matching = self.ids[start..]
+            .iter()
+            .take_while(|(id, _)| id.starts_with(link));
+        match (matching.next(), matching.next()) {
+            (Some((_, i)), None) => Some(*i),
+            _ => None,
+        }
+    }
+}
+
+/// Finds the partial IDs linked to in some content, in the order they first
+/// appear.
+pub fn extract_links(content: &str) -> Vec<String> {
+    let mut links: Vec<String> = Vec::new();
+    for captures in link_pattern().captures_iter(content) {
+        let link = captures[1].to_lowercase();
+        if link.len() >= MIN_LINK_LENGTH && !links.contains(&link) {
+            links.push(link);
+        }
+    }
+
+    links
+}
+
+/// The notes a note links to, by index in the notes `ids` was built from.
+/// Links that don't match exactly one note are left out.
+pub fn resolve_links(note: &Note, ids: &IdIndex) -> Vec<usize> {
+    let mut targets: Vec<usize> = Vec::new();
+    for link in extract_links(&note.content).iter() {
+        if let Some(target) = ids.resolve(link) {
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+    }
+
+    targets
+}
+
+/// Finds the links in the notes that don't match exactly one note, paired
+/// with the note they're in.
+pub fn dangling_links(notes: &[Note]) -> Vec<(&Note, String)> {
+    let ids = IdIndex::new(notes);
+    notes
+        .iter()
+        .flat_map(|note| {
+            extract_links(&note.content)
+                .into_iter()
+                .filter(|link| ids.resolve(link).is_none())
+                .map(move |link| (note, link))
+        })
+        .collect()
+}
+
+/// Maps the ID of each linked note to the IDs of the notes linking to it.
+pub fn backlinks(notes: &[Note]) -> HashMap<&str, Vec<&str>> {
+    let ids = IdIndex::new(notes);
+    let mut linked_from: HashMap<&str, Vec<&str>> = HashMap::new();
+    for note in notes.iter() {
+        for target in resolve_links(note, &ids) {
+            if notes[target].id != note.id {
+                linked_from
+                    .entry(&notes[target].id)
+                    .or_default()
+                    .push(&note.id);
+            }
+        }
+    }
+
+    linked_from
+}
+
+/// Warns about links in a new or edited note's content that don't match
+/// exactly one note.
+pub fn warn_dangling(content: &str, tacked_dir: &Path) -> Result<(), Box<dyn Error>> {
+    let links = extract_links(content);
+    if links.is_empty() {
+        return Ok(());
+    }
+    let notes = get_notes(tacked_dir)?;
+    for link in links.iter() {
+        if let Err(e) = find_note(&notes, link) {
+            eprintln!("Warning: link [[{}]] doesn't lead to a note. {}", link, e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Quotes a string as a DOT ID.
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Writes the notes as a Graphviz DOT graph. Links are solid arrows, replies
+/// are dotted arrows to the note they answer and, if `files` is set, notes
+/// have dashed arrows to the files they're on.
+pub fn to_dot(notes: &[Note], files: bool) -> String {
+    let mut lines = vec![
+        String::from("digraph notes {"),
+        String::from("    rankdir=LR;"),
+        String::from("    node [shape=box];"),
+    ];
+    let ids: BTreeSet<&str> = notes.iter().map(|n| n.id.as_str()).collect();
+    for note in notes.iter() {
+        let first_line = note.content.lines().next().unwrap_or("");
+        let mut label = format!("({}) {}", note.short_id(), first_line);
+        if label.chars().count() > 40 {
+            label = label.chars().take(37).collect::<String>() + "...";
+        }
+        lines.push(format!(
+            "    {} [label={}];",
+            quote(&note.id),
+            quote(&label)
+        ));
+    }
+    let mut on_files: BTreeSet<String> = BTreeSet::new();
+    let index = IdIndex::new(notes);
+    for note in notes.iter() {
+        for target in resolve_links(note, &index) {
+            lines.push(format!(
+                "    {} -> {};",
+                quote(&note.id),
+                quote(&notes[target].id)
+            ));
+        }
+        if let Some(ref parent) = note.parent {
+            if ids.contains(parent.as_str()) {
+                lines.push(format!(
+                    "    {} -> {} [style=dotted];",
+                    quote(&note.id),
+                    quote(parent)
+                ));
+            }
+        }
+        if let (true, Some(on)) = (files, note.on.as_ref()) {
+            let file = on.to_string_lossy().into_owned();
+            let label = match note.anchor {
+                Some(ref anchor) => {
+                    format!(" [style=dashed, label=\"{}-{}\"]", anchor.start, anchor.end)
+                }
+                None => String::from(" [style=dashed]"),
+            };
+            lines.push(format!(
+                "    {} -> {}{};",
+                quote(&note.id),
+                quote(&format!("file:{}", file)),
+                label
+            ));
+            on_files.insert(file);
+        }
+    }
+    for file in on_files.iter() {
+        lines.push(format!(
+            "    {} [label={}, shape=note];",
+            quote(&format!("file:{}", file)),
+            quote(file)
+        ));
+    }
+    lines.push(String::from("}"));
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn find_and_draw_links() {
+        let mut target = Note::new(String::from("The parser is slow."));
+        target.id = String::from("a1b2c3d4e5f60718293a4b5c6d7e8f90");
+        target.on = Some(PathBuf::from("src/parser.rs"));
+        let mut linking = Note::new(String::from(
+            "See [[A1B2C3]] and [[a1b2c3]], but not [[ffff0000]] or [[abc]].",
+        ));
+        linking.id = String::from("0123456789abcdef0123456789abcdef");
+        assert_eq!(extract_links(&linking.content), vec!["a1b2c3", "ffff0000"]);
+        let notes = vec![target, linking];
+        let dangling = dangling_links(&notes);
+        assert_eq!(dangling.len(), 1);
+        assert_eq!(dangling[0].1, "ffff0000");
+        let ids = IdIndex::new(&notes);
+        assert_eq!(ids.resolve("a1b2"), Some(0));
+        assert_eq!(ids.resolve("ffff"), None);
+        let mut twin = notes[0].clone();
+        twin.id = String::from("a1b2ffff");
+        let twins = vec![notes[0].clone(), twin];
+        assert_eq!(IdIndex::new(&twins).resolve("a1b2"), None);
+        let linked_from = backlinks(&notes);
+        assert_eq!(
+            linked_from[notes[0].id.as_str()],
+            vec![notes[1].id.as_str()]
+        );
+        let dot = to_dot(&notes, true);
+        assert!(dot.starts_with("digraph notes {"));
+        assert!(dot.contains(&format!("\"{}\" -> \"{}\";", notes[1].id, notes[0].id)));
+        assert!(dot.contains("\"file:src/parser.rs\" [label=\"src/parser.rs\", shape=note];"));
+        assert!(!to_dot(&notes, false).contains("file:"));
+    }
+}
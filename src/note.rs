@@ -3,35 +3,99 @@
 /// A `tack-it-on` note.,
 use chrono;
 use clap;
-use serde_json;
-use std::collections::hash_map::DefaultHasher;
 use std::env;
 use std::error::Error;
-use std::fs::{File, OpenOptions};
-use std::hash::{Hash, Hasher};
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::fs;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use subprocess::Exec;
 use tempfile::NamedTempFile;
+use uuid::Uuid;
 
-use init::find_tacked_notes;
+use anchor::{split_line_range, Anchor};
+use author::current_author;
+use due::{parse_due, today};
+use global::{is_global, resolve_tacked_dir};
+use links::warn_dangling;
+use recur::Recurrence;
+use store::{lock_notes, open_store};
+use tags::{add_tags, extract_hashtags, normalise_tag};
 
-#[derive(Clone, Debug, Hash, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Note {
+    /// Persistent identifier, assigned when the note is created. Notes saved
+    /// by older versions of `tack-it-on` have no ID and are given one when
+    /// they are next loaded.
+    #[serde(default)]
+    pub id: String,
     pub user: Option<String>,
+    /// The author's email, if known.
+    #[serde(default)]
+    pub email: Option<String>,
     pub content: String,
     pub on: Option<PathBuf>,
     pub todo: Option<(i8, bool)>,
     pub datetime: chrono::DateTime<chrono::Local>,
+    /// When the to do item was marked as done.
+    #[serde(default)]
+    pub completed: Option<chrono::DateTime<chrono::Local>>,
+    /// When the note was last edited.
+    #[serde(default)]
+    pub modified: Option<chrono::DateTime<chrono::Local>>,
+    /// Lowercase tags without a leading `#`, including any `#hashtags` from
+    /// the content.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// The lines of the `on` file the note is tacked onto, if any.
+    #[serde(default)]
+    pub anchor: Option<Anchor>,
+    /// When the to do item is due.
+    #[serde(default)]
+    pub due: Option<chrono::NaiveDate>,
+    /// How often the to do item repeats once it's done.
+    #[serde(default)]
+    pub recur: Option<Recurrence>,
+    /// The ID of the note this is a reply to.
+    #[serde(default)]
+    pub parent: Option<String>,
 }
 
 impl Note {
-    /// Creates an ID for a note by hashing the contents.
-    pub fn gen_id(&self) -> String {
-        let mut h = DefaultHasher::new();
-        self.hash(&mut h);
+    /// Creates a plain note with a new ID. The author is added with
+    /// `Author::sign`.
+    pub fn new(content: String) -> Note {
+        Note {
+            id: Note::gen_id(),
+            user: None,
+            email: None,
+            content,
+            on: None,
+            todo: None,
+            datetime: chrono::Local::now(),
+            completed: None,
+            modified: None,
+            tags: Vec::new(),
+            anchor: None,
+            due: None,
+            recur: None,
+            parent: None,
+        }
+    }
+
+    /// Creates a new, random ID for a note.
+    pub fn gen_id() -> String {
+        Uuid::new_v4().simple().to_string()
+    }
 
-        h.finish().to_string()
+    /// Whether a string can be a note ID, i.e. it is non-empty lowercase
+    /// hexadecimal. IDs become file names, so anything else is refused.
+    pub fn is_valid_id(id: &str) -> bool {
+        !id.is_empty() && id.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+    }
+
+    /// The abbreviated ID that is shown to the user.
+    pub fn short_id(&self) -> &str {
+        &self.id[..8.min(self.id.len())]
     }
 
     pub fn full_note(&self) -> String {
@@ -39,38 +103,73 @@ impl Note {
         // Header
         let date_string = &self.datetime.format("%Y-%m-%d %H:%M:%S").to_string();
         let todo_info = match self.todo {
-            Some((priority, _)) => format!("TO DO p{}", priority),
+            Some((priority, false)) => format!("TO DO p{}", priority),
+            Some((priority, true)) => format!("DONE p{}", priority),
             None => String::from(""),
         };
         if let Some(ref username) = self.user {
+            let author = match self.email {
+                Some(ref email) => format!("{} <{}>", username, email),
+                None => username.clone(),
+            };
             note_string.push_str(&format!(
                 "({}) {} {} {}\n",
-                &self.gen_id()[..8],
+                self.short_id(),
                 todo_info,
-                username,
+                author,
                 date_string
             ));
         } else {
-            note_string.push_str(&format!("({}) {}\n", &self.gen_id()[..8], date_string));
+            note_string.push_str(&format!("({}) {}\n", self.short_id(), date_string));
         }
         // Body
+        if let Some(ref parent) = self.parent {
+            note_string.push_str(&format!("Reply to: ({})\n", &parent[..8.min(parent.len())]));
+        }
         if let Some(ref on_file) = self.on {
-            note_string.push_str(&format!("On: {}\n", on_file.display()));
+            match self.anchor {
+                Some(ref anchor) => note_string.push_str(&format!(
+                    "On: {}:{}-{}\n{}\n",
+                    on_file.display(),
+                    anchor.start,
+                    anchor.end,
+                    anchor.snippet()
+                )),
+                None => note_string.push_str(&format!("On: {}\n", on_file.display())),
+            }
+        }
+        if let Some(ref due) = self.due {
+            note_string.push_str(&format!("Due: {}\n", due.format("%a %Y-%m-%d")));
+        }
+        if let Some(ref recur) = self.recur {
+            note_string.push_str(&format!("Repeats: {}\n", recur));
+        }
+        if !self.tags.is_empty() {
+            note_string.push_str(&format!("Tags: {}\n", self.tags.join(", ")));
+        }
+        if let Some(ref modified) = self.modified {
+            note_string.push_str(&format!(
+                "Modified: {}\n",
+                modified.format("%Y-%m-%d %H:%M:%S")
+            ));
         }
-        note_string.push_str(&format!("{}", &self.content));
+        if let Some(ref completed) = self.completed {
+            note_string.push_str(&format!(
+                "Completed: {}\n",
+                completed.format("%Y-%m-%d %H:%M:%S")
+            ));
+        }
+        note_string.push_str(&self.content);
         note_string
     }
 
     pub fn oneliner(&self) -> String {
         let mut note_string: String = String::new();
         // Header
-        note_string.push_str(&format!("({}) ", &self.gen_id()[..8]));
-        note_string.push_str(&format!(
-            "{}",
-            &self.content.split('\n').collect::<Vec<&str>>()[0]
-        ));
+        note_string.push_str(&format!("({}) ", self.short_id()));
+        note_string.push_str(self.content.split('\n').next().unwrap_or(""));
         note_string.truncate(76);
-        note_string.push_str(&format!("..."));
+        note_string.push_str("...");
         note_string
     }
 
@@ -80,13 +179,10 @@ impl Note {
                 let mut note_string: String = String::new();
                 let status_string = if *complete { "V" } else { " " };
                 // Header
-                note_string.push_str(&format!("[{}] ({}) ", status_string, &self.gen_id()[..8]));
-                note_string.push_str(&format!(
-                    "{}",
-                    &self.content.split('\n').collect::<Vec<&str>>()[0]
-                ));
+                note_string.push_str(&format!("[{}] ({}) ", status_string, self.short_id()));
+                note_string.push_str(self.content.split('\n').next().unwrap_or(""));
                 note_string.truncate(76);
-                note_string.push_str(&format!("..."));
+                note_string.push_str("...");
                 Some((priority, note_string))
             }
             None => None,
@@ -95,113 +191,173 @@ impl Note {
 }
 
 /// Main entry point to the `note` subcommand. Creates a new note.
-pub fn run_note(input: &clap::ArgMatches) -> Result<(), Box<Error>> {
-    let cwd = Path::new(".").canonicalize()?;
-    let maybe_tacked = find_tacked_notes(&cwd)?;
-
-    if let Some(mut tacked_dir) = maybe_tacked {
-        let maybe_on = input.value_of("on");
-        let maybe_todo: Option<(i8, bool)> = if input.is_present("todo") {
-            if input.is_present("priority") {
-                let priority = input
-                    .value_of("priority")
-                    .unwrap_or("3")
-                    .parse::<i8>()
-                    .map_err(|_| {
-                        format!(
-                            "Priority outside possible range of {} to {}.",
-                            <i8>::min_value(),
-                            <i8>::max_value()
-                        )
-                    })?;
-                Some((priority, false))
-            } else {
-                None
-            }
+pub fn run_note(input: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let tacked_dir = resolve_tacked_dir(input)?;
+    let maybe_on = input.value_of("on");
+    let maybe_due = match input.value_of("due") {
+        Some(due) => Some(parse_due(due, today())?),
+        None => None,
+    };
+    let maybe_recur: Option<Recurrence> = match input.value_of("recur") {
+        Some(recur) => Some(recur.parse()?),
+        None => None,
+    };
+    let maybe_todo: Option<(i8, bool)> =
+        if input.is_present("todo") || maybe_due.is_some() || maybe_recur.is_some() {
+            let priority = parse_priority(input.value_of("priority").unwrap_or("3"))?;
+            Some((priority, false))
         } else {
             None
         };
-        let note = match input.value_of("note") {
-            Some(content) => String::from(content),
-            None => get_content_from_editor()?,
+    let note = match input.value_of("note") {
+        Some(content) => String::from(content),
+        None => get_content_from_editor("")?,
+    };
+    if note.split_whitespace().next().is_some() {
+        let tags: Vec<String> = match input.values_of("tag") {
+            Some(tags) => tags.map(normalise_tag).collect(),
+            None => Vec::new(),
         };
-        if note.split_whitespace().collect::<Vec<&str>>().len() > 0 {
-            create_note(note, maybe_on, maybe_todo, &mut tacked_dir)
-        } else {
-            Err(From::from("Note has no content. Aborting."))
+        let mut note = Note::new(note);
+        note.todo = maybe_todo;
+        note.due = maybe_due;
+        note.recur = maybe_recur;
+        let content = note.content.clone();
+        tack_note(note, maybe_on, &tags, &tacked_dir)?;
+        warn_dangling(&content, &tacked_dir)?;
+        if is_global(&tacked_dir) && !input.is_present("global") {
+            println!("No `.tacked` directory found, added a global note.");
         }
+        Ok(())
     } else {
-        Err(From::from(
-            "No `.tacked` directory found. Run `init` before adding notes.",
-        ))
+        Err(From::from("Note has no content. Aborting."))
     }
 }
 
-/// Collects note contents from editor.
-fn get_content_from_editor() -> Result<String, Box<Error>> {
+/// Parses a to do item priority.
+pub fn parse_priority(priority: &str) -> Result<i8, String> {
+    priority.parse::<i8>().map_err(|_| {
+        format!(
+            "Priority outside possible range of {} to {}.",
+            i8::MIN,
+            i8::MAX
+        )
+    })
+}
+
+/// Collects note contents from editor, starting from the initial content
+/// supplied.
+pub fn get_content_from_editor(initial: &str) -> Result<String, Box<dyn Error>> {
     let editor = match env::vars().find(|(key, _)| key == "EDITOR") {
         Some((_, val)) => val,
         None => String::from("vi"),
     };
-    let tmpfile = NamedTempFile::new()?;
+    let mut tmpfile = NamedTempFile::new()?;
+    tmpfile.write_all(initial.as_bytes())?;
+    tmpfile.flush()?;
     Exec::cmd(editor).arg(tmpfile.path()).join()?;
-    let mut file = tmpfile.as_file();
-    file.seek(SeekFrom::Start(0)).unwrap();
-    let mut buf = String::new();
-    file.read_to_string(&mut buf).unwrap();
-    Ok(String::from(buf))
+    // Read back by path, as some editors replace the file rather than
+    // writing to it in place.
+    let buf = fs::read_to_string(tmpfile.path())?;
+    Ok(buf)
 }
 
-/// Creates and stores a new note.
+/// Creates and stores a new note, as a shorthand for tests.
+#[cfg(test)]
 pub fn create_note(
     content: String,
     maybe_on: Option<&str>,
     maybe_todo: Option<(i8, bool)>,
-    tacked_dir: &PathBuf,
-) -> Result<(), Box<Error>> {
-    let (notes_path, mut notes) = get_notes(&tacked_dir)?;
-    let user = env::vars().find(|(key, _)| key == "USER").map(|x| x.1);
-    let maybe_short_on = short_on_path(maybe_on, tacked_dir)?;
-    let note = Note {
-        user,
-        content,
-        on: maybe_short_on,
-        todo: maybe_todo,
-        datetime: chrono::Local::now(),
-    };
-    notes.push(note);
-    save_notes(&notes, &notes_path)?;
+    tags: &[String],
+    tacked_dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let mut note = Note::new(content);
+    note.todo = maybe_todo;
+    tack_note(note, maybe_on, tags, tacked_dir)
+}
+
+/// Stores a new note by the current author, tacked onto the `--on` flag
+/// target if there is one.
+///
+/// Any `#hashtags` in the content are added to the tags supplied.
+pub fn tack_note(
+    mut note: Note,
+    maybe_on: Option<&str>,
+    tags: &[String],
+    tacked_dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let _lock = lock_notes(tacked_dir)?;
+    let mut store = open_store(tacked_dir)?;
+    current_author(tacked_dir).sign(&mut note);
+    let (maybe_short_on, anchor) = resolve_on(maybe_on, tacked_dir)?;
+    note.on = maybe_short_on;
+    note.anchor = anchor;
+    let hashtags = extract_hashtags(&note.content);
+    add_tags(&mut note, tags.iter().cloned().chain(hashtags));
+    store.insert(&note)?;
 
     Ok(())
 }
 
-/// Gets all notes from `notes.json` in the `.tacked` folder.
-pub fn get_notes(tacked_dir: &PathBuf) -> Result<(PathBuf, Vec<Note>), Box<Error>> {
-    let notes: Vec<Note>;
-    let mut notes_path = tacked_dir.clone();
-    notes_path.push("notes.json");
-    if notes_path.exists() {
-        let mut notes_file = File::open(&notes_path)?;
-        let mut notes_string = String::new();
-        notes_file.read_to_string(&mut notes_string)?;
-        notes = serde_json::from_str(&notes_string)?;
-    } else {
-        notes = Vec::new();
+/// Gets all notes from the project's note store.
+pub fn get_notes(tacked_dir: &Path) -> Result<Vec<Note>, Box<dyn Error>> {
+    open_store(tacked_dir)?.all()
+}
+
+/// Finds the index of the note matching a partial ID.
+///
+/// The partial ID must be the start of exactly one note ID.
+pub fn find_note(notes: &[Note], id: &str) -> Result<usize, Box<dyn Error>> {
+    let id = id.to_lowercase();
+    let (mut matching_indices, matching_ids): (Vec<usize>, Vec<&str>) = notes
+        .iter()
+        .enumerate()
+        .filter(|(_, n)| !id.is_empty() && n.id.starts_with(&id))
+        .map(|(i, n)| (i, n.short_id()))
+        .unzip();
+    match matching_ids.len() {
+        0 => Err(From::from("No notes matching that ID.")),
+        1 => Ok(matching_indices.pop().unwrap()),
+        _ => Err(From::from(format!(
+            "ID portion not unique, increase length. Could be:\n    {}",
+            matching_ids.join("\n    ")
+        ))),
     }
+}
 
-    Ok((notes_path, notes))
+/// Resolves a `--on` flag target, which may end with a line range such as
+/// `src/lib.rs:40-55`, into a path and an anchor to those lines.
+pub fn resolve_on(
+    maybe_on: Option<&str>,
+    tacked_dir: &Path,
+) -> Result<(Option<PathBuf>, Option<Anchor>), Box<dyn Error>> {
+    let (path, range) = match maybe_on {
+        Some(on) => split_line_range(on)?,
+        None => return Ok((None, None)),
+    };
+    let anchor = match range {
+        Some((start, end)) => Some(Anchor::new(Path::new(path), start, end)?),
+        None => None,
+    };
+
+    Ok((short_on_path(Some(path), tacked_dir)?, anchor))
 }
 
 /// Returns the `--on` flag target path, relative to the `.tacked` directory.
-fn short_on_path(
+///
+/// Global notes aren't part of a project, so their paths are kept absolute.
+pub fn short_on_path(
     maybe_on: Option<&str>,
-    tacked_dir: &PathBuf,
-) -> Result<Option<PathBuf>, Box<Error>> {
+    tacked_dir: &Path,
+) -> Result<Option<PathBuf>, Box<dyn Error>> {
     let mut maybe_short_on = None;
     if let Some(on_string) = maybe_on {
         let on_path = Path::new(on_string)
             .canonicalize()
-            .expect(&format!("Could not find '{}'.", on_string));
+            .map_err(|_| format!("Could not find '{}'.", on_string))?;
+        if is_global(tacked_dir) {
+            return Ok(Some(on_path));
+        }
         let tacked_parent = tacked_dir.parent().expect("`.tacked` has no parent dir.");
         let mut path_after_tacked = PathBuf::new();
         let mut post_tacked = false;
@@ -225,19 +381,6 @@ fn short_on_path(
     Ok(maybe_short_on)
 }
 
-/// Writes an updated `notes.json` file to the `.tacked` directory.
-pub fn save_notes(notes: &Vec<Note>, notes_path: &PathBuf) -> Result<(), Box<Error>> {
-    let notes_json = serde_json::to_string(notes)?;
-    let mut buffer = OpenOptions::new()
-        .write(true)
-        .truncate(true)
-        .create(true)
-        .open(notes_path)?;
-    buffer.write(&notes_json.into_bytes())?;
-
-    Ok(())
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -251,12 +394,54 @@ mod tests {
         fs::create_dir(tacked_path.clone()).unwrap();
         let content = String::from("This is a test note.");
         let maybe_on = None;
-        create_note(content.clone(), maybe_on, &tacked_path).unwrap();
+        create_note(content.clone(), maybe_on, None, &[], &tacked_path).unwrap();
         let json_path = tacked_path.join("notes.json");
         assert!(json_path.exists());
-        let (notes_path, mut notes) = get_notes(&tacked_path).unwrap();
-        assert_eq!(notes_path, json_path);
+        let mut notes = get_notes(&tacked_path).unwrap();
         let note = notes.pop().unwrap();
         assert_eq!(note.content, content);
     }
+
+    #[test]
+    fn concurrent_notes_are_kept() {
+        let temp_dir = TempDir::new("lock_test").expect("Could not create temp directory.");
+        let tacked_path = temp_dir.path().join(".tacked");
+        fs::create_dir(tacked_path.clone()).unwrap();
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let tacked_path = tacked_path.clone();
+                ::std::thread::spawn(move || {
+                    create_note(format!("Note {}", i), None, None, &[], &tacked_path).unwrap();
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        let notes = get_notes(&tacked_path).unwrap();
+        assert_eq!(notes.len(), 8);
+        let leftovers = fs::read_dir(&tacked_path).unwrap().count();
+        assert_eq!(leftovers, 2);
+    }
+
+    #[test]
+    fn ids_are_persistent() {
+        let temp_dir = TempDir::new("id_test").expect("Could not create temp directory.");
+        let tacked_path = temp_dir.path().join(".tacked");
+        fs::create_dir(tacked_path.clone()).unwrap();
+        let legacy = r#"[{"user":null,"content":"Old note.","on":null,"todo":null,
+            "datetime":"2017-10-08T23:21:48.390531318+01:00"}]"#;
+        fs::write(tacked_path.join("notes.json"), legacy).unwrap();
+        let first = get_notes(&tacked_path).unwrap();
+        assert_eq!(first[0].id.len(), 32);
+        let mut second = get_notes(&tacked_path).unwrap();
+        assert_eq!(first[0].id, second[0].id);
+        second[0].content = String::from("Changed content.");
+        open_store(&tacked_path)
+            .unwrap()
+            .update(&second[0])
+            .unwrap();
+        let third = get_notes(&tacked_path).unwrap();
+        assert_eq!(find_note(&third, &first[0].id[..4]).unwrap(), 0);
+    }
 }
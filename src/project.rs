@@ -0,0 +1,81 @@
+//! This module contains functions for looking at the files of the project
+//! that notes are tacked onto.
+
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use subprocess::{Exec, Redirection};
+use walkdir::WalkDir;
+
+/// Files larger than this aren't read when searching the project.
+const MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+/// Runs git in a directory, returning its output if it succeeds.
+///
+/// Returns `None` if git isn't installed or the directory isn't in a
+/// repository.
+pub fn git_output(dir: &Path, args: &[&str]) -> Option<String> {
+    let captured = Exec::cmd("git")
+        .args(args)
+        .cwd(dir)
+        .stdout(Redirection::Pipe)
+        .stderr(Redirection::Pipe)
+        .capture()
+        .ok()?;
+    if captured.exit_status.success() {
+        Some(captured.stdout_str())
+    } else {
+        None
+    }
+}
+
+/// Lists the files in a project that are small enough to search, relative to
+/// the project directory.
+///
+/// In a git repository, files ignored by git are left out. Hidden files and
+/// directories such as `.git` and `.tacked` are always left out.
+pub fn project_files(project_dir: &Path) -> Vec<PathBuf> {
+    let listed = git_output(
+        project_dir,
+        &[
+            "ls-files",
+            "-z",
+            "--cached",
+            "--others",
+            "--exclude-standard",
+        ],
+    );
+    let files: Vec<PathBuf> = match listed {
+        Some(listed) => listed
+            .split('\0')
+            .filter(|f| !f.is_empty())
+            .map(PathBuf::from)
+            .collect(),
+        None => WalkDir::new(project_dir)
+            .into_iter()
+            .filter_entry(|e| e.depth() == 0 || !is_hidden(Path::new(e.file_name())))
+            .flatten()
+            .filter_map(|e| {
+                e.path()
+                    .strip_prefix(project_dir)
+                    .ok()
+                    .map(Path::to_path_buf)
+            })
+            .collect(),
+    };
+    files
+        .into_iter()
+        .filter(|f| !is_hidden(f))
+        .filter(|f| {
+            fs::metadata(project_dir.join(f)).is_ok_and(|m| m.is_file() && m.len() <= MAX_FILE_SIZE)
+        })
+        .collect()
+}
+
+/// Whether any part of a path starts with `.`.
+fn is_hidden(path: &Path) -> bool {
+    path.components().any(|c| match c {
+        Component::Normal(name) => name.to_string_lossy().starts_with('.'),
+        _ => false,
+    })
+}
@@ -0,0 +1,383 @@
+//! This module contains a small filter language for selecting notes, e.g.
+//!
+//! ```text
+//! tag:bug and priority>=4 and author:alice and created>2026-01-01 and on:src/**
+//! is:open and due<=2026-10-23
+//! ```
+//!
+//! Terms are `field:value` or `field<op>value` with `op` one of `=`, `!=`,
+//! `>`, `>=`, `<` and `<=`. Terms can be combined with `and`, `or`, `not` and
+//! parentheses, and terms next to each other are joined with `and`. A word
+//! that isn't a term matches notes containing it.
+
+use std::path::Path;
+
+use chrono::NaiveDate;
+use glob::{MatchOptions, Pattern};
+
+use author::is_by;
+use note::Note;
+use tags::normalise_tag;
+
+/// A parsed filter expression.
+#[derive(Debug)]
+pub enum Query {
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+    Term(Term),
+}
+
+/// A single condition on a note.
+#[derive(Debug)]
+pub enum Term {
+    Tag(String),
+    Priority(Op, i8),
+    Author(String),
+    Created(Op, NaiveDate),
+    Modified(Op, NaiveDate),
+    Due(Op, NaiveDate),
+    On(String),
+    Id(String),
+    Is(Status),
+    Text(String),
+}
+
+/// Comparison operators. `:` is treated as `=`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+/// Values for `is:`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Status {
+    Todo,
+    Open,
+    Done,
+}
+
+impl Op {
+    fn compare<T: PartialOrd>(self, a: T, b: T) -> bool {
+        match self {
+            Op::Eq => a == b,
+            Op::Ne => a != b,
+            Op::Gt => a > b,
+            Op::Ge => a >= b,
+            Op::Lt => a < b,
+            Op::Le => a <= b,
+        }
+    }
+}
+
+impl Query {
+    /// Parses a filter expression.
+    pub fn parse(input: &str) -> Result<Query, String> {
+        let tokens = tokenise(input)?;
+        if tokens.is_empty() {
+            return Err(String::from("Filter is empty."));
+        }
+        let mut parser = Parser { tokens, pos: 0 };
+        let query = parser.parse_or()?;
+        match parser.tokens.get(parser.pos) {
+            Some(token) => Err(format!("Unexpected '{}' in filter.", token.text())),
+            None => Ok(query),
+        }
+    }
+
+    pub fn matches(&self, note: &Note) -> bool {
+        match self {
+            Query::And(a, b) => a.matches(note) && b.matches(note),
+            Query::Or(a, b) => a.matches(note) || b.matches(note),
+            Query::Not(a) => !a.matches(note),
+            Query::Term(term) => term.matches(note),
+        }
+    }
+}
+
+impl Term {
+    pub fn matches(&self, note: &Note) -> bool {
+        match self {
+            Term::Tag(tag) => note.tags.contains(tag),
+            Term::Priority(op, priority) => match note.todo {
+                Some((p, _)) => op.compare(p, *priority),
+                None => false,
+            },
+            Term::Author(author) => is_by(note, author),
+            Term::Created(op, date) => op.compare(note.datetime.date_naive(), *date),
+            Term::Modified(op, date) => note
+                .modified
+                .is_some_and(|m| op.compare(m.date_naive(), *date)),
+            Term::Due(op, date) => note.due.is_some_and(|due| op.compare(due, *date)),
+            Term::On(pattern) => note.on.as_ref().is_some_and(|on| on_matches(on, pattern)),
+            Term::Id(id) => note.id.starts_with(id.as_str()),
+            Term::Is(Status::Todo) => note.todo.is_some(),
+            Term::Is(Status::Open) => note.todo.is_some_and(|(_, done)| !done),
+            Term::Is(Status::Done) => note.todo.is_some_and(|(_, done)| done),
+            Term::Text(text) => note.content.to_lowercase().contains(text.as_str()),
+        }
+    }
+}
+
+/// Whether a note's path is matched by a glob pattern, or lies within the
+/// path given.
+pub fn on_matches(on: &Path, pattern: &str) -> bool {
+    let pattern = pattern.trim_end_matches('/');
+    let options = MatchOptions {
+        case_sensitive: true,
+        require_literal_separator: true,
+        require_literal_leading_dot: false,
+    };
+    on.starts_with(pattern)
+        || Pattern::new(pattern).is_ok_and(|p| p.matches_path_with(on, &options))
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Word(String),
+}
+
+impl Token {
+    fn text(&self) -> &str {
+        match self {
+            Token::Open => "(",
+            Token::Close => ")",
+            Token::Word(word) => word,
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        match self {
+            Token::Word(word) => word.eq_ignore_ascii_case(keyword),
+            _ => false,
+        }
+    }
+}
+
+/// Splits a filter into words and parentheses. Double quotes group text
+/// containing spaces into one word.
+fn tokenise(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            '(' => {
+                tokens.push(Token::Open);
+                chars.next();
+            }
+            ')' => {
+                tokens.push(Token::Close);
+                chars.next();
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            _ => {
+                let mut word = String::new();
+                let mut quoted = false;
+                while let Some(&c) = chars.peek() {
+                    if c == '"' {
+                        quoted = !quoted;
+                    } else if !quoted && (c.is_whitespace() || c == '(' || c == ')') {
+                        break;
+                    } else {
+                        word.push(c);
+                    }
+                    chars.next();
+                }
+                if quoted {
+                    return Err(String::from("Unclosed quote in filter."));
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn parse_or(&mut self) -> Result<Query, String> {
+        let mut query = self.parse_and()?;
+        while self.peek().is_some_and(|t| t.is_keyword("or")) {
+            self.pos += 1;
+            let rhs = self.parse_and()?;
+            query = Query::Or(Box::new(query), Box::new(rhs));
+        }
+        Ok(query)
+    }
+
+    fn parse_and(&mut self) -> Result<Query, String> {
+        let mut query = self.parse_not()?;
+        loop {
+            match self.peek() {
+                Some(t) if t.is_keyword("and") => self.pos += 1,
+                // Adjacent terms are implicitly joined with `and`.
+                Some(t) if *t != Token::Close && !t.is_keyword("or") => (),
+                _ => break,
+            }
+            let rhs = self.parse_not()?;
+            query = Query::And(Box::new(query), Box::new(rhs));
+        }
+        Ok(query)
+    }
+
+    fn parse_not(&mut self) -> Result<Query, String> {
+        if self.peek().is_some_and(|t| t.is_keyword("not")) {
+            self.pos += 1;
+            return Ok(Query::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_atom()
+    }
+
+    fn parse_atom(&mut self) -> Result<Query, String> {
+        self.pos += 1;
+        match self.tokens.get(self.pos - 1) {
+            Some(Token::Open) => {
+                let query = self.parse_or()?;
+                match self.peek() {
+                    Some(Token::Close) => {
+                        self.pos += 1;
+                        Ok(query)
+                    }
+                    _ => Err(String::from("Missing ')' in filter.")),
+                }
+            }
+            Some(Token::Word(word)) => Ok(Query::Term(parse_term(word)?)),
+            Some(Token::Close) => Err(String::from("Unexpected ')' in filter.")),
+            None => Err(String::from("Filter ends unexpectedly.")),
+        }
+    }
+}
+
+/// Parses a single `field<op>value` term, or a bare word.
+fn parse_term(word: &str) -> Result<Term, String> {
+    let split = word.find([':', '=', '!', '<', '>']);
+    let (field, rest) = match split {
+        Some(i) if i > 0 => word.split_at(i),
+        _ => return Ok(Term::Text(word.to_lowercase())),
+    };
+    let (op, value) = if let Some(value) = rest.strip_prefix(">=") {
+        (Op::Ge, value)
+    } else if let Some(value) = rest.strip_prefix("<=") {
+        (Op::Le, value)
+    } else if let Some(value) = rest.strip_prefix("!=") {
+        (Op::Ne, value)
+    } else if let Some(value) = rest.strip_prefix('>') {
+        (Op::Gt, value)
+    } else if let Some(value) = rest.strip_prefix('<') {
+        (Op::Lt, value)
+    } else if let Some(value) = rest.strip_prefix(':').or_else(|| rest.strip_prefix('=')) {
+        (Op::Eq, value)
+    } else {
+        return Err(format!("Could not understand '{}' in filter.", word));
+    };
+    if value.is_empty() {
+        return Err(format!("No value given for '{}' in filter.", field));
+    }
+    let equality_only = |term: Term| {
+        if op == Op::Eq {
+            Ok(term)
+        } else {
+            Err(format!("'{}' can only be matched with ':'.", field))
+        }
+    };
+    match &*field.to_lowercase() {
+        "tag" => equality_only(Term::Tag(normalise_tag(value))),
+        "priority" | "p" => {
+            let priority = value
+                .parse()
+                .map_err(|_| format!("'{}' is not a valid priority.", value))?;
+            Ok(Term::Priority(op, priority))
+        }
+        "author" | "user" => equality_only(Term::Author(value.to_lowercase())),
+        "created" | "date" => Ok(Term::Created(op, parse_date(value)?)),
+        "modified" => Ok(Term::Modified(op, parse_date(value)?)),
+        "due" => Ok(Term::Due(op, parse_date(value)?)),
+        "on" => equality_only(Term::On(String::from(value))),
+        "id" => equality_only(Term::Id(value.to_lowercase())),
+        "is" => match &*value.to_lowercase() {
+            "todo" => equality_only(Term::Is(Status::Todo)),
+            "open" => equality_only(Term::Is(Status::Open)),
+            "done" => equality_only(Term::Is(Status::Done)),
+            _ => Err(format!(
+                "Unknown status '{}', expected todo, open or done.",
+                value
+            )),
+        },
+        "text" | "content" => equality_only(Term::Text(value.to_lowercase())),
+        _ => Err(format!("Unknown filter field '{}'.", field)),
+    }
+}
+
+fn parse_date(value: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| format!("'{}' is not a date, expected YYYY-MM-DD.", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+    use std::path::PathBuf;
+
+    fn test_note() -> Note {
+        Note {
+            id: String::from("a1b2c3d4e5f60718293a4b5c6d7e8f90"),
+            user: Some(String::from("Alice")),
+            email: None,
+            content: String::from("This loop is slow."),
+            on: Some(PathBuf::from("src/note.rs")),
+            todo: Some((4, false)),
+            datetime: Local.with_ymd_and_hms(2026, 2, 1, 12, 0, 0).unwrap(),
+            completed: None,
+            modified: None,
+            tags: vec![String::from("bug"), String::from("perf")],
+            anchor: None,
+            due: NaiveDate::from_ymd_opt(2026, 2, 10),
+            recur: None,
+            parent: None,
+        }
+    }
+
+    #[test]
+    fn match_queries() {
+        let note = test_note();
+        let matches = |q: &str| Query::parse(q).unwrap().matches(&note);
+        assert!(matches(
+            "tag:bug and priority>=4 and author:alice and created>2026-01-01 and on:src/**"
+        ));
+        assert!(matches("tag:docs or (is:open not is:done)"));
+        assert!(matches("on:src loop"));
+        assert!(!matches("not tag:perf"));
+        assert!(!matches("priority<4 or is:done"));
+        assert!(!matches("on:src/*.txt"));
+        assert!(matches("id:A1B2 text:\"loop is\""));
+        assert!(matches("due<=2026-02-10 and not due<2026-02-10"));
+    }
+
+    #[test]
+    fn reject_bad_queries() {
+        assert!(Query::parse("").is_err());
+        assert!(Query::parse("(tag:bug").is_err());
+        assert!(Query::parse("tag>bug").is_err());
+        assert!(Query::parse("colour:red").is_err());
+        assert!(Query::parse("created>yesterday").is_err());
+    }
+}
@@ -0,0 +1,215 @@
+//! Recurrence rules for to do items that repeat, such as monthly chores.
+//!
+//! Rules can be given as `daily`, `weekly`, `monthly` or `yearly`, as
+//! `every 3 days` or `every 2 months`, or as a subset of iCalendar's RRULE
+//! such as `FREQ=WEEKLY;INTERVAL=2`.
+
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{Duration, Months, NaiveDate};
+
+/// How often a recurrence repeats.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+impl Frequency {
+    fn unit(self) -> &'static str {
+        match self {
+            Frequency::Daily => "day",
+            Frequency::Weekly => "week",
+            Frequency::Monthly => "month",
+            Frequency::Yearly => "year",
+        }
+    }
+
+    fn from_unit(unit: &str) -> Option<Frequency> {
+        match unit.trim_end_matches('s') {
+            "day" => Some(Frequency::Daily),
+            "week" => Some(Frequency::Weekly),
+            "month" => Some(Frequency::Monthly),
+            "year" => Some(Frequency::Yearly),
+            _ => None,
+        }
+    }
+}
+
+/// A rule for when a to do item is next due once it's done.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Recurrence {
+    pub frequency: Frequency,
+    /// The number of days, weeks, months or years between occurrences.
+    pub interval: u32,
+}
+
+impl Recurrence {
+    /// The date one interval after `date`, or `None` if that's beyond the
+    /// calendar. Monthly and yearly dates that don't exist, such as 31
+    /// February, become the last day of the month.
+    pub fn after(self, date: NaiveDate) -> Option<NaiveDate> {
+        let interval = self.interval.max(1);
+        match self.frequency {
+            Frequency::Daily => date.checked_add_signed(Duration::try_days(interval as i64)?),
+            Frequency::Weekly => date.checked_add_signed(Duration::try_weeks(interval as i64)?),
+            Frequency::Monthly => date.checked_add_months(Months::new(interval)),
+            Frequency::Yearly => date.checked_add_months(Months::new(interval.checked_mul(12)?)),
+        }
+    }
+
+    /// The due date of the next occurrence of an item that was due on `due`
+    /// and is done `today`. Occurrences missed while the item was overdue are
+    /// skipped, so the next one is always after today.
+    pub fn next_due(self, due: Option<NaiveDate>, today: NaiveDate) -> Option<NaiveDate> {
+        let mut next = self.after(due.unwrap_or(today))?;
+        while next <= today {
+            next = self.after(next)?;
+        }
+
+        Some(next)
+    }
+
+    /// The rule as an iCalendar RRULE value.
+    pub fn to_rrule(self) -> String {
+        let frequency = match self.frequency {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+            Frequency::Yearly => "YEARLY",
+        };
+        format!("FREQ={};INTERVAL={}", frequency, self.interval)
+    }
+
+    fn from_rrule(rule: &str) -> Option<Recurrence> {
+        let mut frequency = None;
+        let mut interval = 1;
+        for part in rule.split(';').filter(|p| !p.is_empty()) {
+            let (key, value) = part.split_once('=')?;
+            match &*key.to_uppercase() {
+                "FREQ" => {
+                    frequency = match &*value.to_uppercase() {
+                        "DAILY" => Some(Frequency::Daily),
+                        "WEEKLY" => Some(Frequency::Weekly),
+                        "MONTHLY" => Some(Frequency::Monthly),
+                        "YEARLY" => Some(Frequency::Yearly),
+                        _ => return None,
+                    }
+                }
+                "INTERVAL" => interval = value.parse().ok()?,
+                // Other parts, such as BYDAY, aren't supported.
+                _ => return None,
+            }
+        }
+        Some(Recurrence {
+            frequency: frequency?,
+            interval,
+        })
+    }
+
+    /// The rule as a todo.txt `rec:` value, e.g. `2w` for every two weeks.
+    pub fn to_todotxt(self) -> String {
+        format!("{}{}", self.interval, &self.frequency.unit()[..1])
+    }
+
+    /// Reads a todo.txt `rec:` value. Strict recurrence, written with a
+    /// leading `+`, is treated like any other.
+    pub fn from_todotxt(value: &str) -> Option<Recurrence> {
+        let value = value.trim_start_matches('+');
+        let unit = value.chars().last()?;
+        let interval = value[..value.len() - unit.len_utf8()]
+            .parse()
+            .ok()
+            .filter(|i| *i > 0)?;
+        let frequency = match unit {
+            'd' => Frequency::Daily,
+            'w' => Frequency::Weekly,
+            'm' => Frequency::Monthly,
+            'y' => Frequency::Yearly,
+            _ => return None,
+        };
+        Some(Recurrence {
+            frequency,
+            interval,
+        })
+    }
+}
+
+impl FromStr for Recurrence {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Recurrence, String> {
+        let text = s.trim().to_lowercase();
+        let rule = text.strip_prefix("rrule:").unwrap_or(&text);
+        let words: Vec<&str> = rule.split_whitespace().collect();
+        let recurrence = match words[..] {
+            ["daily"] => Some((Frequency::Daily, 1)),
+            ["weekly"] => Some((Frequency::Weekly, 1)),
+            ["monthly"] => Some((Frequency::Monthly, 1)),
+            ["yearly"] => Some((Frequency::Yearly, 1)),
+            ["every", unit] => Frequency::from_unit(unit).map(|f| (f, 1)),
+            ["every", count, unit] => match (count.parse(), Frequency::from_unit(unit)) {
+                (Ok(interval), Some(frequency)) => Some((frequency, interval)),
+                _ => None,
+            },
+            _ => Recurrence::from_rrule(rule).map(|r| (r.frequency, r.interval)),
+        };
+        match recurrence {
+            Some((frequency, interval)) if interval > 0 => Ok(Recurrence {
+                frequency,
+                interval,
+            }),
+            _ => Err(format!(
+                "Could not understand recurrence '{}'. Use daily, weekly, monthly, \
+                 yearly, 'every 3 days' or an RRULE such as FREQ=WEEKLY;INTERVAL=2.",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.interval, self.frequency) {
+            (1, Frequency::Daily) => write!(f, "daily"),
+            (1, Frequency::Weekly) => write!(f, "weekly"),
+            (1, Frequency::Monthly) => write!(f, "monthly"),
+            (1, Frequency::Yearly) => write!(f, "yearly"),
+            (n, frequency) => write!(f, "every {} {}s", n, frequency.unit()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_advance_recurrences() {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let monthly: Recurrence = "monthly".parse().unwrap();
+        assert_eq!(monthly.after(date(2026, 1, 31)), Some(date(2026, 2, 28)));
+        let fortnightly: Recurrence = "FREQ=WEEKLY;INTERVAL=2".parse().unwrap();
+        assert_eq!(fortnightly, "every 2 weeks".parse().unwrap());
+        assert_eq!(fortnightly.to_string(), "every 2 weeks");
+        assert_eq!(fortnightly.to_rrule(), "FREQ=WEEKLY;INTERVAL=2");
+        let every_3_days: Recurrence = "every 3 days".parse().unwrap();
+        let today = date(2026, 10, 17);
+        assert_eq!(every_3_days.next_due(None, today), Some(date(2026, 10, 20)));
+        assert_eq!(
+            every_3_days.next_due(Some(date(2026, 10, 10)), today),
+            Some(date(2026, 10, 19))
+        );
+        let huge: Recurrence = "every 4000000000 years".parse().unwrap();
+        assert_eq!(huge.next_due(None, today), None);
+        let far: Recurrence = "every 4000000000 days".parse().unwrap();
+        assert_eq!(far.next_due(None, today), None);
+        assert!("every 0 days".parse::<Recurrence>().is_err());
+        assert!("FREQ=WEEKLY;BYDAY=MO".parse::<Recurrence>().is_err());
+        assert!("sometimes".parse::<Recurrence>().is_err());
+    }
+}
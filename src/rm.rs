@@ -1,67 +1,142 @@
 //! This module contains functionality for removing notes.
 
+use std::collections::HashSet;
 use std::error::Error;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 
 use clap;
 
-use init::find_tacked_notes;
-use note::{get_notes, save_notes};
+use global::resolve_tacked_dir;
+use init::ask_yes_no;
+use note::{find_note, Note};
+use query::Query;
+use store::{lock_notes, open_store};
+use thread::{descendants, reparent};
 
 /// Main entry point to the `rm` subcommand.
-pub fn run_rm(input: &clap::ArgMatches) -> Result<(), Box<Error>> {
-    let cwd = Path::new(".").canonicalize()?;
-    let maybe_tacked = find_tacked_notes(&cwd)?;
-    if let Some(tacked_dir) = maybe_tacked {
-        if let Some(id) = input.value_of("id") {
-            remove_note(id, &tacked_dir)?;
+pub fn run_rm(input: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let tacked_dir = resolve_tacked_dir(input)?;
+    let cascade = input.is_present("cascade");
+    let confirm = !input.is_present("yes");
+    if let Some(id) = input.value_of("id") {
+        let count = remove_note(id, cascade, confirm, &tacked_dir)?;
+        if count == 1 {
+            println!("Removed note.");
+        } else {
+            println!("Removed {} note(s).", count);
         }
-    } else {
-        return Err(From::from(
-            "No `.tacked` directory found. Run `init` to add notes.",
-        ));
+    } else if let Some(filter) = input.value_of("where") {
+        let query = Query::parse(filter)?;
+        let count = remove_matching(&query, cascade, confirm, &tacked_dir)?;
+        println!("Removed {} note(s).", count);
     }
 
     Ok(())
 }
 
-/// Removes a note given a partial ID.
-fn remove_note(id: &str, tacked_dir: &PathBuf) -> Result<(), Box<Error>> {
-    let (notes_path, mut notes) = get_notes(&tacked_dir)?;
-    let note_ids: Vec<String> = notes.iter().map(|n| n.gen_id()).collect();
-    let (mut matching_indices, mut matching_ids): (Vec<usize>, Vec<String>) = note_ids
+/// Removes a note given a partial ID, returning the number of notes removed.
+///
+/// If `cascade` is set, replies to the note are removed too, asking first if
+/// `confirm` is set. Otherwise they're moved up to the note's parent.
+fn remove_note(
+    id: &str,
+    cascade: bool,
+    confirm: bool,
+    tacked_dir: &Path,
+) -> Result<usize, Box<dyn Error>> {
+    let notes = open_store(tacked_dir)?.all()?;
+    let chosen: HashSet<String> = vec![notes[find_note(&notes, id)?].id.clone()]
         .into_iter()
-        .enumerate()
-        .filter(|(_, r)| &r[..id.len()] == id)
-        .unzip();
-    for id_string in matching_ids.iter_mut() {
-        id_string.truncate(8);
+        .collect();
+    let confirm = confirm && cascade && !descendants(&notes, &chosen).is_empty();
+    match confirm_removal(&notes, chosen, cascade, confirm) {
+        Some(chosen) => remove_chosen(&chosen, tacked_dir),
+        None => Ok(0),
     }
-    match matching_ids.len() {
-        0 => return Err(From::from("No notes matching that ID.")),
-        1 => (),
-        _ => {
-            return Err(From::from(format!(
-                "ID portion not unique, increase length. Could be:\n    {}",
-                matching_ids.join("\n    ")
-            )))
+}
+
+/// Removes every note matched by a filter, returning the number removed.
+///
+/// If `confirm` is set, the matching notes are listed and the user is asked
+/// before anything is removed. Replies are handled as by `remove_note`.
+fn remove_matching(
+    query: &Query,
+    cascade: bool,
+    confirm: bool,
+    tacked_dir: &Path,
+) -> Result<usize, Box<dyn Error>> {
+    let notes = open_store(tacked_dir)?.all()?;
+    let chosen: HashSet<String> = notes
+        .iter()
+        .filter(|n| query.matches(n))
+        .map(|n| n.id.clone())
+        .collect();
+    if chosen.is_empty() {
+        return Ok(0);
+    }
+    match confirm_removal(&notes, chosen, cascade, confirm) {
+        Some(chosen) => remove_chosen(&chosen, tacked_dir),
+        None => Ok(0),
+    }
+}
+
+/// Adds the replies to the chosen notes if `cascade` is set, then lists them
+/// and asks before they're removed if `confirm` is set. Returns `None` if the
+/// answer is no.
+///
+/// This is done without the notes lock, so that other `tack` commands aren't
+/// held up while waiting for an answer.
+fn confirm_removal(
+    notes: &[Note],
+    mut chosen: HashSet<String>,
+    cascade: bool,
+    confirm: bool,
+) -> Option<HashSet<String>> {
+    if cascade {
+        let replies = descendants(notes, &chosen);
+        chosen.extend(replies);
+    }
+    if confirm {
+        for note in notes.iter().filter(|n| chosen.contains(&n.id)) {
+            println!("{}", note.oneliner());
+        }
+        if !ask_yes_no(&format!("Remove these {} note(s)?", chosen.len())) {
+            return None;
         }
     }
-    if let Some(i) = matching_indices.pop() {
-        notes.remove(i);
-        println!("Removed note.");
+
+    Some(chosen)
+}
+
+/// Removes the chosen notes, returning the number removed. Replies that are
+/// kept are moved up to the nearest note in their thread that is kept.
+///
+/// The notes are read again under the lock, as they may have changed since
+/// they were chosen, and chosen notes that are already gone are skipped.
+fn remove_chosen(chosen: &HashSet<String>, tacked_dir: &Path) -> Result<usize, Box<dyn Error>> {
+    let _lock = lock_notes(tacked_dir)?;
+    let mut store = open_store(tacked_dir)?;
+    let notes = store.all()?;
+    let chosen: HashSet<String> = notes
+        .iter()
+        .filter(|n| chosen.contains(&n.id))
+        .map(|n| n.id.clone())
+        .collect();
+    store.update_all(&reparent(&notes, &chosen))?;
+    for id in chosen.iter() {
+        store.remove(id)?;
     }
-    save_notes(&notes, &notes_path)?;
 
-    Ok(())
+    Ok(chosen.len())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use note::create_note;
+    use note::{create_note, get_notes};
     use std::fs;
     use tempdir::TempDir;
+    use thread::reply_to;
 
     #[test]
     fn rm_note() {
@@ -70,11 +145,54 @@ mod tests {
         fs::create_dir(tacked_path.clone()).unwrap();
         let content = String::from("This is a test note.");
         let maybe_on = None;
-        create_note(content.clone(), maybe_on, &tacked_path).unwrap();
-        let (notes_path, mut notes) = get_notes(&tacked_path).unwrap();
+        create_note(content.clone(), maybe_on, None, &[], &tacked_path).unwrap();
+        let mut notes = get_notes(&tacked_path).unwrap();
         let note = notes.pop().unwrap();
-        remove_note(&note.gen_id(), &tacked_path).unwrap();
-        let (_, notes) = get_notes(&tacked_path).unwrap();
+        assert_eq!(
+            remove_note(&note.id, false, false, &tacked_path).unwrap(),
+            1
+        );
+        let notes = get_notes(&tacked_path).unwrap();
         assert_eq!(notes.len(), 0);
     }
+
+    #[test]
+    fn rm_matching_notes() {
+        let temp_dir = TempDir::new("rm_test").expect("Could not create temp directory.");
+        let tacked_path = temp_dir.path().join(".tacked");
+        fs::create_dir(tacked_path.clone()).unwrap();
+        let tags = vec![String::from("old")];
+        create_note(String::from("One."), None, None, &tags, &tacked_path).unwrap();
+        create_note(String::from("Two."), None, None, &tags, &tacked_path).unwrap();
+        create_note(String::from("Three."), None, None, &[], &tacked_path).unwrap();
+        let query = Query::parse("tag:old").unwrap();
+        assert_eq!(
+            remove_matching(&query, false, false, &tacked_path).unwrap(),
+            2
+        );
+        let notes = get_notes(&tacked_path).unwrap();
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].content, "Three.");
+    }
+
+    #[test]
+    fn rm_threads() {
+        let temp_dir = TempDir::new("rm_test").expect("Could not create temp directory.");
+        let tacked_path = temp_dir.path().join(".tacked");
+        fs::create_dir(tacked_path.clone()).unwrap();
+        create_note(String::from("Question."), None, None, &[], &tacked_path).unwrap();
+        let question = get_notes(&tacked_path).unwrap()[0].id.clone();
+        reply_to(&question, String::from("Answer."), &[], &tacked_path).unwrap();
+        let answer = get_notes(&tacked_path).unwrap()[1].id.clone();
+        reply_to(&answer, String::from("Thanks."), &[], &tacked_path).unwrap();
+        assert_eq!(remove_note(&answer, false, false, &tacked_path).unwrap(), 1);
+        let notes = get_notes(&tacked_path).unwrap();
+        assert_eq!(notes[1].content, "Thanks.");
+        assert_eq!(notes[1].parent.as_ref(), Some(&question));
+        assert_eq!(
+            remove_note(&question, true, false, &tacked_path).unwrap(),
+            2
+        );
+        assert!(get_notes(&tacked_path).unwrap().is_empty());
+    }
 }
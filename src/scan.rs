@@ -0,0 +1,339 @@
+//! This module contains the `scan` subcommand, which turns `TODO`, `FIXME`
+//! and `XXX` comments in the project's files into to do items.
+//!
+//! Each item is anchored to the line of the comment, or to the line the
+//! comment was above if comments are removed. Items that have already been
+//! imported are skipped, so `scan` can be run repeatedly.
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono;
+use clap;
+use regex::{self, Regex};
+
+use anchor::Anchor;
+use author::{current_author, Author};
+use config::load_config;
+use global::is_global;
+use init::{ask_yes_no, find_tacked_notes};
+use note::Note;
+use project::project_files;
+use store::{lock_notes, open_store, write_atomic};
+use tags::{add_tags, extract_hashtags, normalise_tag};
+
+/// Markers used when the project doesn't configure its own.
+const DEFAULT_MARKERS: [&str; 3] = ["TODO", "FIXME", "XXX"];
+
+/// A marked comment found in a file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Found {
+    pub file: PathBuf,
+    /// Line number, starting from 1.
+    pub line: usize,
+    pub marker: String,
+    pub text: String,
+    /// Whether the whole line is the comment, so can be removed. Comments
+    /// after code, or starting a block comment, are left in place.
+    removable: bool,
+}
+
+/// Main entry point for the `scan` subcommand.
+pub fn run_scan(input: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let cwd = Path::new(".").canonicalize()?;
+    let tacked_dir = find_tacked_notes(&cwd)?
+        .ok_or("No `.tacked` directory found. Run `tack init` in the project first.")?;
+    let markers: Vec<String> = match input.values_of("marker") {
+        Some(markers) => markers.map(String::from).collect(),
+        None => load_config(&tacked_dir)?.scan_markers,
+    };
+    let found = new_comments(&scan_project(&tacked_dir, &markers)?, &tacked_dir)?;
+    if found.is_empty() {
+        println!("No new comments found.");
+        return Ok(());
+    }
+    for comment in found.iter() {
+        println!(
+            "{}:{}: {} {}",
+            comment.file.display(),
+            comment.line,
+            comment.marker,
+            comment.text
+        );
+    }
+    let remove = input.is_present("remove")
+        && (input.is_present("yes")
+            || ask_yes_no(&format!(
+                "Remove these {} comment(s) from the files?",
+                found.len()
+            )));
+    let count = import_comments(found, &markers, remove, &tacked_dir)?;
+    println!("Added {} to do item(s).", count);
+
+    Ok(())
+}
+
+/// Builds a pattern matching a comment starting with one of the markers. The
+/// comment must start the line or follow whitespace, so that markers in
+/// strings such as `"#TODO"` aren't mistaken for comments.
+fn comment_pattern(markers: &[String]) -> Result<Regex, Box<dyn Error>> {
+    let markers: Vec<String> = if markers.is_empty() {
+        DEFAULT_MARKERS.iter().map(|m| regex::escape(m)).collect()
+    } else {
+        markers.iter().map(|m| regex::escape(m)).collect()
+    };
+    let pattern = format!(
+        r"^(?P<code>|.*?\s)(?P<open>//+|#+|/\*+|\*|--|;+|<!--|%+)\s*(?P<marker>{})\b(?:\([^)]*\))?:?\s*(?P<text>.*?)\s*(?P<close>\*/|-->)?\s*$",
+        markers.join("|")
+    );
+
+    Ok(Regex::new(&pattern)?)
+}
+
+/// Finds the marked comments in a file's contents. Markdown only has HTML
+/// comments, as `#` starts a heading there.
+fn scan_text(file: &Path, contents: &str, pattern: &Regex) -> Vec<Found> {
+    let markdown = match file.extension().and_then(|e| e.to_str()) {
+        Some(extension) => ["md", "markdown"].contains(&&*extension.to_lowercase()),
+        None => false,
+    };
+    let mut found = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        let captures = match pattern.captures(line) {
+            Some(captures) => captures,
+            None => continue,
+        };
+        let open = &captures["open"];
+        if markdown && open != "<!--" {
+            continue;
+        }
+        let closed = captures.name("close").is_some();
+        // Removing the start of a block comment would break the file.
+        let block = open.starts_with("/*") || open == "<!--";
+        let removable = captures["code"].trim().is_empty() && (closed || !block);
+        let marker = String::from(&captures["marker"]);
+        let text = captures["text"].trim();
+        found.push(Found {
+            file: file.to_path_buf(),
+            line: i + 1,
+            text: if text.is_empty() {
+                marker.clone()
+            } else {
+                String::from(text)
+            },
+            marker,
+            removable,
+        });
+    }
+
+    found
+}
+
+/// Finds the marked comments in every file of the project.
+pub fn scan_project(tacked_dir: &Path, markers: &[String]) -> Result<Vec<Found>, Box<dyn Error>> {
+    if is_global(tacked_dir) {
+        return Err(From::from("Global notes aren't part of a project to scan."));
+    }
+    let project_dir = tacked_dir.parent().expect("`.tacked` has no parent dir.");
+    let pattern = comment_pattern(markers)?;
+    let mut found = Vec::new();
+    for file in project_files(project_dir) {
+        // Binary files aren't valid UTF-8 and are skipped.
+        if let Ok(contents) = fs::read_to_string(project_dir.join(&file)) {
+            found.extend(scan_text(&file, &contents, &pattern));
+        }
+    }
+
+    Ok(found)
+}
+
+/// Leaves out the comments that have already been imported, which are to do
+/// items with the same content on the same file.
+fn new_comments(found: &[Found], tacked_dir: &Path) -> Result<Vec<Found>, Box<dyn Error>> {
+    let imported: HashSet<(PathBuf, String)> = open_store(tacked_dir)?
+        .query(&|n| n.todo.is_some() && n.on.is_some())?
+        .into_iter()
+        .map(|n| (n.on.expect("Filtered to notes on files."), n.content))
+        .collect();
+
+    Ok(found
+        .iter()
+        .filter(|f| !imported.contains(&(f.file.clone(), f.text.clone())))
+        .cloned()
+        .collect())
+}
+
+/// Adds a to do item for each comment, optionally removing the comments from
+/// the files first. Returns the number of items added.
+///
+/// The project is scanned again under the notes lock, which is held until the
+/// items are stored, and only the comments that are still there and still new
+/// are imported. Another `scan` may have got to them first while the user was
+/// asked about removing them.
+pub fn import_comments(
+    found: Vec<Found>,
+    markers: &[String],
+    remove: bool,
+    tacked_dir: &Path,
+) -> Result<usize, Box<dyn Error>> {
+    let _lock = lock_notes(tacked_dir)?;
+    let found: Vec<Found> = new_comments(&scan_project(tacked_dir, markers)?, tacked_dir)?
+        .into_iter()
+        .filter(|f| found.contains(f))
+        .collect();
+    let project_dir = tacked_dir.parent().expect("`.tacked` has no parent dir.");
+    let author = current_author(tacked_dir);
+    let mut notes = Vec::new();
+    let mut files: Vec<&Path> = found.iter().map(|f| f.file.as_path()).collect();
+    files.dedup();
+    for file in files {
+        let path = project_dir.join(file);
+        let in_file: Vec<&Found> = found.iter().filter(|f| f.file == file).collect();
+        // Where each comment's note is anchored, once any comments are removed.
+        let mut lines: Vec<usize> = in_file.iter().map(|f| f.line).collect();
+        if remove {
+            let contents = fs::read_to_string(&path)?;
+            // Lines keep their own endings, so CRLF files stay CRLF.
+            let new_contents: String = contents
+                .split_inclusive('\n')
+                .enumerate()
+                .filter(|(i, _)| !in_file.iter().any(|f| f.line == i + 1 && f.removable))
+                .map(|(_, line)| line)
+                .collect();
+            let mut removed = 0;
+            for (comment, line) in in_file.iter().zip(lines.iter_mut()) {
+                *line -= removed;
+                if comment.removable {
+                    removed += 1;
+                }
+            }
+            write_atomic(&path, new_contents.as_bytes())?;
+        }
+        let line_count = fs::read_to_string(&path)?.lines().count();
+        for (comment, line) in in_file.into_iter().zip(lines) {
+            // A comment on the last line has nothing after it to anchor to.
+            let line = line.min(line_count);
+            let anchor = if line > 0 {
+                Some(Anchor::new(&path, line, line)?)
+            } else {
+                None
+            };
+            notes.push(comment_note(comment, anchor, &author));
+        }
+    }
+    let mut store = open_store(tacked_dir)?;
+    store.insert_all(&notes)?;
+
+    Ok(notes.len())
+}
+
+fn comment_note(comment: &Found, anchor: Option<Anchor>, author: &Author) -> Note {
+    let mut note = Note {
+        id: Note::gen_id(),
+        user: None,
+        email: None,
+        content: comment.text.clone(),
+        on: Some(comment.file.clone()),
+        todo: Some((3, false)),
+        datetime: chrono::Local::now(),
+        completed: None,
+        modified: None,
+        tags: Vec::new(),
+        anchor,
+        due: None,
+        recur: None,
+        parent: None,
+    };
+    author.sign(&mut note);
+    let hashtags = extract_hashtags(&comment.text);
+    add_tags(
+        &mut note,
+        Some(normalise_tag(&comment.marker))
+            .into_iter()
+            .chain(hashtags),
+    );
+
+    note
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use note::get_notes;
+    use tempdir::TempDir;
+
+    #[test]
+    fn find_marked_comments() {
+        let pattern = comment_pattern(&[]).unwrap();
+        let contents =
+            "fn main() {\n    // TODO: Handle errors.\n    run(); # FIXME(bob) slow\n    \
+                        /* XXX */\n    let todo = 1;\n    /* TODO: spans\n    \
+                        println!(\"# TODO: later\");\n}\n";
+        let found = scan_text(Path::new("main.rs"), contents, &pattern);
+        assert_eq!(found.len(), 4);
+        assert_eq!(
+            (found[0].line, found[0].text.as_str()),
+            (2, "Handle errors.")
+        );
+        assert!(found[0].removable);
+        assert_eq!(found[1].marker, "FIXME");
+        assert!(!found[1].removable);
+        assert_eq!(found[2].text, "XXX");
+        assert!(found[2].removable);
+        assert!(!found[3].removable);
+    }
+
+    #[test]
+    fn import_and_remove_comments() {
+        let temp_dir = TempDir::new("scan_test").expect("Could not create temp directory.");
+        let tacked_path = temp_dir.path().join(".tacked");
+        fs::create_dir(tacked_path.clone()).unwrap();
+        let kept = temp_dir.path().join("kept.rs");
+        fs::write(&kept, "// FIXME: Kept.\n").unwrap();
+        let found = scan_project(&tacked_path, &[]).unwrap();
+        // Comments imported by another scan in the meantime are skipped.
+        let twice = vec![found[0].clone(), found[0].clone()];
+        assert_eq!(import_comments(found, &[], false, &tacked_path).unwrap(), 1);
+        assert_eq!(import_comments(twice, &[], false, &tacked_path).unwrap(), 0);
+        let again = scan_project(&tacked_path, &[]).unwrap();
+        assert_eq!(again.len(), 1);
+        assert!(new_comments(&again, &tacked_path).unwrap().is_empty());
+        let removed = temp_dir.path().join("removed.rs");
+        fs::write(
+            &removed,
+            "fn main() {\r\n    // TODO: Handle errors.\r\n    run(); // TODO: Speed up.\r\n}",
+        )
+        .unwrap();
+        let custom = scan_project(&tacked_path, &[String::from("Handle")]).unwrap();
+        assert!(custom.is_empty());
+        let found = new_comments(&scan_project(&tacked_path, &[]).unwrap(), &tacked_path).unwrap();
+        assert_eq!(import_comments(found, &[], true, &tacked_path).unwrap(), 2);
+        assert_eq!(
+            fs::read_to_string(&removed).unwrap(),
+            "fn main() {\r\n    run(); // TODO: Speed up.\r\n}"
+        );
+        assert_eq!(fs::read_to_string(&kept).unwrap(), "// FIXME: Kept.\n");
+        let readme = temp_dir.path().join("README.md");
+        fs::write(
+            &readme,
+            "# Project\n\n## TODO list\n<!-- TODO: Write intro. -->\n",
+        )
+        .unwrap();
+        let found = new_comments(&scan_project(&tacked_path, &[]).unwrap(), &tacked_path).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(import_comments(found, &[], true, &tacked_path).unwrap(), 1);
+        assert_eq!(
+            fs::read_to_string(&readme).unwrap(),
+            "# Project\n\n## TODO list\n"
+        );
+        let notes = get_notes(&tacked_path).unwrap();
+        let anchor = notes[1].anchor.as_ref().unwrap();
+        assert_eq!(
+            (anchor.start, anchor.lines[0].as_str()),
+            (2, "    run(); // TODO: Speed up.")
+        );
+        assert_eq!(notes[1].tags, vec!["todo"]);
+    }
+}
@@ -0,0 +1,331 @@
+//! This module contains the `search` subcommand, which finds notes by the
+//! words in their content, `on` path and tags.
+//!
+//! Words are looked up in an inverted index kept in `.tacked/index.json`. The
+//! index records the revision of the store it was built from and is rebuilt
+//! whenever the notes change.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::ops::Bound;
+use std::path::Path;
+
+use clap;
+use regex::{Regex, RegexBuilder};
+use serde_json;
+
+use global::resolve_tacked_dir;
+use note::Note;
+use store::{open_store, store_revision, write_atomic};
+
+/// Increased whenever the layout of `index.json` changes.
+const INDEX_VERSION: u32 = 1;
+
+/// How much a word counts towards a note's relevance, depending on where in
+/// the note it was found.
+const CONTENT_WEIGHT: u32 = 1;
+const ON_WEIGHT: u32 = 2;
+const TAG_WEIGHT: u32 = 3;
+
+/// An inverted index from words to the notes that contain them.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Index {
+    version: u32,
+    /// The store revision the index was built from.
+    revision: String,
+    /// The number of notes indexed.
+    note_count: usize,
+    /// Maps each word to the IDs of the notes containing it, along with a
+    /// weighted count of its occurrences.
+    postings: BTreeMap<String, BTreeMap<String, u32>>,
+}
+
+impl Index {
+    fn build(notes: &[Note], revision: String) -> Index {
+        let mut index = Index {
+            version: INDEX_VERSION,
+            revision,
+            note_count: notes.len(),
+            postings: BTreeMap::new(),
+        };
+        for note in notes {
+            let mut add = |text: &str, weight: u32| {
+                for word in words(text) {
+                    *index
+                        .postings
+                        .entry(word)
+                        .or_default()
+                        .entry(note.id.clone())
+                        .or_insert(0) += weight;
+                }
+            };
+            add(&note.content, CONTENT_WEIGHT);
+            if let Some(ref on) = note.on {
+                add(&on.to_string_lossy(), ON_WEIGHT);
+            }
+            for tag in note.tags.iter() {
+                add(tag, TAG_WEIGHT);
+            }
+        }
+
+        index
+    }
+
+    /// Scores the notes containing a word, or any word starting with it.
+    ///
+    /// Rarer words score more highly, using a TF-IDF style weighting.
+    fn score(&self, term: &str) -> BTreeMap<&str, f64> {
+        let mut scores: BTreeMap<&str, f64> = BTreeMap::new();
+        let matching = self
+            .postings
+            .range::<str, _>((Bound::Included(term), Bound::Unbounded))
+            .take_while(|(word, _)| word.starts_with(term));
+        for (_, notes) in matching {
+            let rarity = (1.0 + self.note_count as f64 / notes.len() as f64).ln();
+            for (id, count) in notes {
+                *scores.entry(id).or_insert(0.0) += f64::from(*count) * rarity;
+            }
+        }
+
+        scores
+    }
+}
+
+/// Splits text into lowercase words.
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .collect()
+}
+
+/// How search terms are matched.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SearchMode {
+    pub regex: bool,
+    pub case_sensitive: bool,
+}
+
+/// Main entry point for the `search` subcommand.
+pub fn run_search(input: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let tacked_dir = resolve_tacked_dir(input)?;
+    let terms: Vec<&str> = input
+        .values_of("terms")
+        .expect("Terms are a required argument.")
+        .collect();
+    let mode = SearchMode {
+        regex: input.is_present("regex"),
+        case_sensitive: input.is_present("case_sensitive"),
+    };
+    let results = search_notes(&terms, mode, &tacked_dir)?;
+    let highlighter = if io::stdout().is_terminal() {
+        Some(match_pattern(&terms, mode)?)
+    } else {
+        None
+    };
+    let notes_strings: Vec<String> = results
+        .iter()
+        .map(|(_, note)| {
+            let note_string = if input.is_present("oneline") {
+                note.oneliner()
+            } else {
+                note.full_note()
+            };
+            match highlighter {
+                Some(ref pattern) => highlight(&note_string, pattern),
+                None => note_string,
+            }
+        })
+        .collect();
+    println!("{}", notes_strings.join("\n"));
+
+    Ok(())
+}
+
+/// Finds the notes matching every search term, most relevant first.
+pub fn search_notes(
+    terms: &[&str],
+    mode: SearchMode,
+    tacked_dir: &Path,
+) -> Result<Vec<(f64, Note)>, Box<dyn Error>> {
+    let store = open_store(tacked_dir)?;
+    if mode.regex {
+        // Regular expressions can't use the index, so every note is checked.
+        let pattern = match_pattern(terms, mode)?;
+        let mut results: Vec<(f64, Note)> = store
+            .all()?
+            .into_iter()
+            .map(|n| (pattern.find_iter(&searchable_text(&n)).count() as f64, n))
+            .filter(|(score, _)| *score > 0.0)
+            .collect();
+        sort_results(&mut results);
+        return Ok(results);
+    }
+    let index = load_index(tacked_dir)?;
+    let mut scores: Option<BTreeMap<&str, f64>> = None;
+    let query_words: BTreeSet<String> = terms.iter().flat_map(|t| words(t)).collect();
+    for word in query_words.iter() {
+        let word_scores = index.score(word);
+        scores = Some(match scores {
+            None => word_scores,
+            // Notes must contain every word.
+            Some(previous) => previous
+                .into_iter()
+                .filter_map(|(id, s)| word_scores.get(id).map(|t| (id, s + t)))
+                .collect(),
+        });
+    }
+    let scores = scores.unwrap_or_default();
+    let ids: Vec<String> = scores.keys().map(|id| String::from(*id)).collect();
+    let mut results: Vec<(f64, Note)> = store
+        .get(&ids)?
+        .into_iter()
+        .map(|n| (scores[n.id.as_str()], n))
+        .collect();
+    if mode.case_sensitive {
+        results.retain(|(_, n)| {
+            let text = searchable_text(n);
+            terms.iter().all(|t| text.contains(t))
+        });
+    }
+    sort_results(&mut results);
+
+    Ok(results)
+}
+
+/// Loads the search index, rebuilding it if the notes have changed since it
+/// was written.
+fn load_index(tacked_dir: &Path) -> Result<Index, Box<dyn Error>> {
+    let index_path = tacked_dir.join("index.json");
+    let revision = store_revision(tacked_dir)?;
+    if index_path.exists() {
+        let index_string = fs::read_to_string(&index_path)?;
+        // An unreadable index is simply rebuilt.
+        if let Ok(index) = serde_json::from_str::<Index>(&index_string) {
+            if index.version == INDEX_VERSION && index.revision == revision {
+                return Ok(index);
+            }
+        }
+    }
+    let notes = open_store(tacked_dir)?.all()?;
+    let index = Index::build(&notes, revision);
+    write_atomic(&index_path, serde_json::to_string(&index)?.as_bytes())?;
+
+    Ok(index)
+}
+
+/// The parts of a note that are searched.
+fn searchable_text(note: &Note) -> String {
+    let mut text = note.content.clone();
+    if let Some(ref on) = note.on {
+        text.push('\n');
+        text.push_str(&on.to_string_lossy());
+    }
+    for tag in note.tags.iter() {
+        text.push('\n');
+        text.push_str(tag);
+    }
+
+    text
+}
+
+/// Sorts results by descending score, then by newest first.
+fn sort_results(results: &mut [(f64, Note)]) {
+    results.sort_by(|(s, n), (t, m)| {
+        t.partial_cmp(s)
+            .unwrap_or(::std::cmp::Ordering::Equal)
+            .then(m.datetime.cmp(&n.datetime))
+    });
+}
+
+/// Builds a pattern matching any of the search terms.
+fn match_pattern(terms: &[&str], mode: SearchMode) -> Result<Regex, Box<dyn Error>> {
+    let alternatives: Vec<String> = if mode.regex {
+        terms.iter().map(|t| format!("(?:{})", t)).collect()
+    } else {
+        terms.iter().map(|t| regex::escape(t)).collect()
+    };
+    let pattern = RegexBuilder::new(&alternatives.join("|"))
+        .case_insensitive(!mode.case_sensitive)
+        .build()?;
+
+    Ok(pattern)
+}
+
+/// Highlights every match of a pattern in bold red.
+fn highlight(text: &str, pattern: &Regex) -> String {
+    pattern
+        .replace_all(text, "\x1b[1;31m$0\x1b[0m")
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use note::create_note;
+    use tempdir::TempDir;
+
+    #[test]
+    fn ranked_search() {
+        let temp_dir = TempDir::new("search_test").expect("Could not create temp directory.");
+        let tacked_path = temp_dir.path().join(".tacked");
+        fs::create_dir(tacked_path.clone()).unwrap();
+        let perf = vec![String::from("perf")];
+        create_note(
+            String::from("The Parser is slow."),
+            None,
+            None,
+            &[],
+            &tacked_path,
+        )
+        .unwrap();
+        create_note(
+            String::from("Parser parser parser."),
+            None,
+            None,
+            &[],
+            &tacked_path,
+        )
+        .unwrap();
+        create_note(String::from("Unrelated."), None, None, &perf, &tacked_path).unwrap();
+        let mode = SearchMode::default();
+        let results = search_notes(&["pars"], mode, &tacked_path).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].1.content, "Parser parser parser.");
+        assert!(tacked_path.join("index.json").exists());
+        let results = search_notes(&["parser", "slow"], mode, &tacked_path).unwrap();
+        assert_eq!(results.len(), 1);
+        // The index must notice new notes.
+        create_note(String::from("Slow start."), None, None, &[], &tacked_path).unwrap();
+        assert_eq!(
+            search_notes(&["slow"], mode, &tacked_path).unwrap().len(),
+            2
+        );
+        assert_eq!(
+            search_notes(&["PERF"], mode, &tacked_path).unwrap().len(),
+            1
+        );
+        let case_sensitive = SearchMode {
+            case_sensitive: true,
+            ..mode
+        };
+        let results = search_notes(&["Parser"], case_sensitive, &tacked_path).unwrap();
+        assert_eq!(results.len(), 2);
+        let results = search_notes(&["The P"], case_sensitive, &tacked_path).unwrap();
+        assert_eq!(results.len(), 1);
+        let regex = SearchMode {
+            regex: true,
+            ..mode
+        };
+        let results = search_notes(&["^(slow|unrelated)"], regex, &tacked_path).unwrap();
+        assert_eq!(results.len(), 2);
+    }
+
+    #[test]
+    fn highlight_matches() {
+        let pattern = match_pattern(&["loop"], SearchMode::default()).unwrap();
+        assert_eq!(highlight("A Loop.", &pattern), "A \x1b[1;31mLoop\x1b[0m.");
+    }
+}
@@ -1,75 +1,244 @@
 //! This module contains functions for showing notes.
 
+use std::collections::HashMap;
 use std::error::Error;
-use std::path::{Path, PathBuf};
+use std::io::{self, IsTerminal};
+use std::path::{Component, Path, PathBuf};
 
+use chrono::NaiveDate;
 use clap;
 
+use anchor::relocate_anchors;
+use author::{current_author, is_by, Author};
+use due::{is_overdue, parse_due, today, todo_line};
+use format::{format_notes, OutputFormat};
+use global::{global_tacked_dir, is_global, resolve_tacked_dir};
 use init::find_tacked_notes;
-use note::get_notes;
+use links::backlinks;
+use note::{get_notes, Note};
+use query::{on_matches, Query};
+use tags::TagFilter;
+use thread::thread_order;
+use tree::render_tree;
+
+/// Options controlling which notes are shown and how.
+#[derive(Default)]
+pub struct ShowOptions {
+    pub oneline: bool,
+    pub todo: bool,
+    /// Groups notes by the files and directories they're on.
+    pub tree: bool,
+    pub tags: TagFilter,
+    pub query: Option<Query>,
+    /// Prefixes each note with the name of the store it came from.
+    pub show_source: bool,
+    pub format: OutputFormat,
+    /// Sorts the to do list by due date rather than priority.
+    pub sort_by_due: bool,
+    /// Only shows to do items due on or before this date.
+    pub due_by: Option<NaiveDate>,
+    /// Only shows open to do items that are past their due date.
+    pub overdue: bool,
+    /// Shows overdue items in red.
+    pub highlight: bool,
+    /// Only shows notes by this author, given by name or email.
+    pub author: Option<String>,
+    /// Only shows notes by this author, matching either their name or email.
+    pub mine: Option<Author>,
+}
 
 /// Main entry point for the `show` subcommand.
-pub fn run_show(input: &clap::ArgMatches) -> Result<(), Box<Error>> {
-    let cwd = Path::new(".").canonicalize()?;
-    let maybe_tacked = find_tacked_notes(&cwd)?;
-    if let Some(tacked_dir) = maybe_tacked {
-        let maybe_on = input.value_of("on");
-        let oneline = input.is_present("oneline");
-        let todo = input.is_present("todo");
-        show_notes(maybe_on, oneline, todo, &tacked_dir)?;
+pub fn run_show(input: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let mut sources: Vec<(&str, PathBuf)> = Vec::new();
+    if input.is_present("all") {
+        let cwd = Path::new(".").canonicalize()?;
+        if let Some(tacked_dir) = find_tacked_notes(&cwd)? {
+            sources.push(("project", tacked_dir));
+        }
+        sources.push(("global", global_tacked_dir()?));
     } else {
-        return Err(From::from(
-            "No `.tacked` directory found. Run `init` before adding notes.",
-        ));
+        sources.push(("", resolve_tacked_dir(input)?));
     }
+    let mut notes: Vec<(&str, Note)> = Vec::new();
+    for (source, tacked_dir) in sources.iter() {
+        let mut source_notes = get_notes(tacked_dir)?;
+        relocate_anchors(&mut source_notes, tacked_dir)?;
+        if let Some(on) = input.value_of("on") {
+            let pattern = resolve_on_pattern(on, tacked_dir)?;
+            let exact = input.is_present("exact");
+            source_notes.retain(|n| {
+                n.on.as_ref().is_some_and(|on_path| {
+                    if exact {
+                        *on_path == pattern
+                    } else {
+                        on_matches(on_path, &pattern.to_string_lossy())
+                    }
+                })
+            });
+        }
+        notes.extend(source_notes.into_iter().map(|n| (*source, n)));
+    }
+    let options = ShowOptions {
+        oneline: input.is_present("oneline"),
+        todo: input.is_present("todo"),
+        tree: input.is_present("tree"),
+        tags: TagFilter::from_args(input),
+        query: match input.value_of("filter") {
+            Some(filter) => Some(Query::parse(filter)?),
+            None => None,
+        },
+        show_source: input.is_present("all"),
+        format: input.value_of("format").unwrap_or("text").parse()?,
+        sort_by_due: input.value_of("sort") == Some("due"),
+        due_by: match input.value_of("due_by") {
+            Some(due_by) => Some(parse_due(due_by, today())?),
+            None => None,
+        },
+        overdue: input.is_present("overdue"),
+        highlight: io::stdout().is_terminal(),
+        author: input.value_of("author").map(String::from),
+        mine: if input.is_present("mine") {
+            let author = current_author(&sources[0].1);
+            if author.is_unknown() {
+                return Err(From::from(
+                    "Could not work out who you are. Set `user.name` in git config.",
+                ));
+            }
+            Some(author)
+        } else {
+            None
+        },
+    };
+    println!("{}", show_notes(notes, &options).join("\n"));
 
     Ok(())
 }
 
-/// Shows all notes.
-fn show_notes(
-    maybe_on: Option<&str>,
-    oneline: bool,
-    todo: bool,
-    tacked_dir: &PathBuf,
-) -> Result<(), Box<Error>> {
-    let (_, notes) = get_notes(tacked_dir)?;
-    let notes_to_print = if let Some(on) = maybe_on {
-        let mut on = String::from(on);
-        if on.ends_with("/") {
-            on.pop();
+/// Resolves a `--on` path or glob pattern given relative to the current
+/// directory into one relative to the project, as stored in notes.
+///
+/// Global notes store absolute paths, so for them the pattern is made
+/// absolute instead.
+fn resolve_on_pattern(on: &str, tacked_dir: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    let cwd = Path::new(".").canonicalize()?;
+    let joined = cwd.join(on);
+    if is_global(tacked_dir) {
+        return Ok(normalise_path(&joined));
+    }
+    let project_dir = tacked_dir.parent().expect("`.tacked` has no parent dir.");
+    match normalise_path(&joined).strip_prefix(project_dir) {
+        Ok(relative) => Ok(relative.to_path_buf()),
+        Err(_) => Err(From::from(format!(
+            "{} is outside of the tack-it-on project.",
+            joined.display()
+        ))),
+    }
+}
+
+/// Removes `.` and `..` from a path without touching the file system, as the
+/// path may be a glob pattern or no longer exist.
+fn normalise_path(path: &Path) -> PathBuf {
+    let mut normalised = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                normalised.pop();
+            }
+            _ => normalised.push(component),
         }
-        notes
-            .iter()
-            .filter(|s| {
-                if let Some(ref on_path) = s.on {
-                    on == on_path.to_str().expect("Could not convert path to str.")
-                } else {
-                    false
-                }
+    }
+
+    normalised
+}
+
+/// Filters notes and formats them for display. Each note is paired with the
+/// name of the store it came from.
+fn show_notes(mut notes: Vec<(&str, Note)>, options: &ShowOptions) -> Vec<String> {
+    let full = !options.todo && !options.oneline && !options.tree;
+    // Found before filtering, so that links from hidden notes are listed too.
+    let linked_from: HashMap<String, Vec<String>> = if full && options.format == OutputFormat::Text
+    {
+        let all: Vec<Note> = notes.iter().map(|(_, n)| n.clone()).collect();
+        backlinks(&all)
+            .into_iter()
+            .map(|(id, from)| {
+                let from = from.iter().map(|f| format!("({})", &f[..8.min(f.len())]));
+                (String::from(id), from.collect())
             })
-            .cloned()
             .collect()
     } else {
-        notes
+        HashMap::new()
     };
-    let notes_strings: Vec<String> = if todo {
-        let mut todos: Vec<(&i8, String)> = notes_to_print
+    notes.retain(|(_, n)| options.tags.matches(n));
+    if let Some(ref author) = options.author {
+        notes.retain(|(_, n)| is_by(n, author));
+    }
+    if let Some(ref author) = options.mine {
+        notes.retain(|(_, n)| author.wrote(n));
+    }
+    if let Some(ref query) = options.query {
+        notes.retain(|(_, n)| query.matches(n));
+    }
+    let today = today();
+    if let Some(due_by) = options.due_by {
+        notes.retain(|(_, n)| n.due.is_some_and(|due| due <= due_by));
+    }
+    if options.overdue {
+        notes.retain(|(_, n)| is_overdue(n, today));
+    }
+    if options.tree {
+        let notes: Vec<Note> = notes.into_iter().map(|(_, n)| n).collect();
+        return render_tree(&notes);
+    }
+    if options.todo {
+        notes.retain(|(_, n)| n.todo.is_some());
+        notes.sort_by_key(|(_, n)| ::std::cmp::Reverse(n.todo.map(|(p, _)| p)));
+        if options.sort_by_due {
+            // Items without a due date go last, in order of priority.
+            notes.sort_by_key(|(_, n)| (n.due.is_none(), n.due));
+        }
+    }
+    if let OutputFormat::Structured(format) = options.format {
+        return format_notes(&notes, format);
+    }
+    let notes_strings: Vec<(&str, String)> = if options.todo {
+        notes
             .iter()
-            .map(|x| x.todo_item())
-            .filter(|x| x.is_some())
-            .map(|x| x.unwrap())
-            .collect();
-        todos.sort_unstable_by(|(p, _), (q, _)| q.cmp(p));
-        todos.into_iter().map(|x| x.1).collect()
-    } else if oneline {
-        notes_to_print.iter().map(|x| x.oneliner()).collect()
+            .filter_map(|(s, n)| todo_line(n, today, options.highlight).map(|t| (*s, t)))
+            .collect()
+    } else if options.oneline {
+        notes.iter().map(|(s, n)| (*s, n.oneliner())).collect()
     } else {
-        notes_to_print.iter().map(|x| x.full_note()).collect()
+        // Replies are indented under the notes they answer.
+        let refs: Vec<&Note> = notes.iter().map(|(_, n)| n).collect();
+        thread_order(&refs)
+            .into_iter()
+            .map(|(i, depth)| {
+                let (source, note) = &notes[i];
+                let indent = "    ".repeat(depth);
+                let mut note_string = note.full_note();
+                if let Some(from) = linked_from.get(&note.id) {
+                    note_string.push_str(&format!("\nLinked from: {}", from.join(", ")));
+                }
+                let lines: Vec<String> = note_string
+                    .lines()
+                    .map(|line| format!("{}{}", indent, line))
+                    .collect();
+                (*source, lines.join("\n"))
+            })
+            .collect()
     };
-    println!("{}", notes_strings.join("\n"));
-
-    Ok(())
+    notes_strings
+        .into_iter()
+        .map(|(source, note_string)| {
+            if options.show_source {
+                format!("{:<8}{}", source, note_string)
+            } else {
+                note_string
+            }
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -86,8 +255,89 @@ mod tests {
         fs::create_dir(tacked_path.clone()).unwrap();
         let content = String::from("This is a test note.");
         let maybe_on = None;
-        let todo = false;
-        create_note(content.clone(), maybe_on, &tacked_path).unwrap();
-        show_notes(maybe_on, todo, &tacked_path).unwrap();
+        create_note(content.clone(), maybe_on, None, &[], &tacked_path).unwrap();
+        let notes = get_notes(&tacked_path)
+            .unwrap()
+            .into_iter()
+            .map(|n| ("", n))
+            .collect();
+        let shown = show_notes(notes, &ShowOptions::default());
+        assert!(shown[0].ends_with(&content));
+    }
+
+    #[test]
+    fn show_sources() {
+        let temp_dir = TempDir::new("show_test").expect("Could not create temp directory.");
+        let tacked_path = temp_dir.path().join(".tacked");
+        fs::create_dir(tacked_path.clone()).unwrap();
+        create_note(String::from("Note."), None, None, &[], &tacked_path).unwrap();
+        let note = get_notes(&tacked_path).unwrap().pop().unwrap();
+        let notes = vec![("project", note.clone()), ("global", note)];
+        let options = ShowOptions {
+            oneline: true,
+            show_source: true,
+            ..Default::default()
+        };
+        let shown = show_notes(notes, &options);
+        assert!(shown[0].starts_with("project ("));
+        assert!(shown[1].starts_with("global  ("));
+    }
+
+    #[test]
+    fn show_linked_from() {
+        let target = Note::new(String::from("Target."));
+        let linking = Note::new(format!("See [[{}]].", target.short_id()));
+        let notes = vec![("", target), ("", linking.clone())];
+        let shown = show_notes(notes, &ShowOptions::default());
+        assert!(shown[0].ends_with(&format!("Linked from: ({})", linking.short_id())));
+        assert!(!shown[1].contains("Linked from"));
+    }
+
+    #[test]
+    fn sort_and_filter_by_due_date() {
+        let today = today();
+        let item = |content: &str, priority: i8, days: Option<i64>| {
+            let mut note = Note::new(String::from(content));
+            note.todo = Some((priority, false));
+            note.due = days.map(|d| today + ::chrono::Duration::days(d));
+            ("", note)
+        };
+        let notes = vec![
+            item("Whenever", 5, None),
+            item("Next week", 4, Some(7)),
+            item("Yesterday", 1, Some(-1)),
+        ];
+        let options = ShowOptions {
+            todo: true,
+            sort_by_due: true,
+            ..Default::default()
+        };
+        let shown = show_notes(notes.clone(), &options);
+        assert!(shown[0].contains("Yesterday") && shown[0].contains("overdue since"));
+        assert!(shown[1].contains("Next week") && shown[1].contains("due "));
+        assert!(shown[2].contains("Whenever"));
+        let options = ShowOptions {
+            todo: true,
+            due_by: Some(today + ::chrono::Duration::days(1)),
+            ..Default::default()
+        };
+        assert_eq!(show_notes(notes.clone(), &options).len(), 1);
+        let options = ShowOptions {
+            overdue: true,
+            ..Default::default()
+        };
+        assert_eq!(show_notes(notes, &options).len(), 1);
+    }
+
+    #[test]
+    fn normalise_on_patterns() {
+        assert_eq!(
+            normalise_path(Path::new("/project/src/../docs/./*.md")),
+            PathBuf::from("/project/docs/*.md")
+        );
+        assert_eq!(
+            normalise_path(Path::new("/project/.")),
+            PathBuf::from("/project")
+        );
     }
 }
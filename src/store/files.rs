@@ -0,0 +1,176 @@
+//! Stores each note in its own file, `.tacked/notes/<id>.json`.
+//!
+//! Notes created on different branches never touch the same file, so a
+//! `.tacked` directory kept under version control merges cleanly. Each file
+//! records the schema version of its note, and older notes are upgraded when
+//! they are read.
+
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::{self, Value};
+
+use note::{find_note, Note};
+use store::schema::{check_version, decode_note, SCHEMA_VERSION};
+use store::{write_atomic, NoteStore};
+
+/// The top level of a note file.
+#[derive(Serialize)]
+struct Envelope<'a> {
+    version: u64,
+    note: &'a Note,
+}
+
+/// Reads a note file, upgrading the note to the current schema.
+fn read_note(path: &Path) -> Result<Note, Box<dyn Error>> {
+    let note_string = fs::read_to_string(path)?;
+    let value: Value = serde_json::from_str(&note_string)
+        .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+    let (version, raw_note) = match value {
+        Value::Object(mut envelope) => {
+            match (envelope.remove("version"), envelope.remove("note")) {
+                (Some(version), Some(raw_note)) => {
+                    let version = version.as_u64().ok_or_else(|| {
+                        format!("{} has an invalid schema version.", path.display())
+                    })?;
+                    (version, raw_note)
+                }
+                // Files written before schema versions held the bare note.
+                _ => (0, Value::Object(envelope)),
+            }
+        }
+        _ => {
+            return Err(From::from(format!(
+                "{} is not a note file.",
+                path.display()
+            )))
+        }
+    };
+    check_version(version, path)?;
+
+    decode_note(raw_note, version)
+}
+
+/// Files in `.tacked` that belong to one checkout and shouldn't be committed.
+const LOCAL_FILES: [&str; 2] = ["lock", "index.json"];
+
+/// Adds the files that shouldn't be committed to `.tacked/.gitignore`, as
+/// this layout is meant to be kept under version control.
+pub fn ignore_local_files(tacked_dir: &Path) -> Result<(), Box<dyn Error>> {
+    let path = tacked_dir.join(".gitignore");
+    let mut contents = if path.exists() {
+        fs::read_to_string(&path)?
+    } else {
+        String::new()
+    };
+    let missing: Vec<&str> = LOCAL_FILES
+        .iter()
+        .filter(|f| !contents.lines().any(|l| l.trim() == **f))
+        .cloned()
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    for file in missing {
+        contents.push_str(file);
+        contents.push('\n');
+    }
+
+    write_atomic(&path, contents.as_bytes())
+}
+
+pub struct FilesStore {
+    notes_dir: PathBuf,
+}
+
+impl FilesStore {
+    /// Opens the `notes` directory, creating it if needed.
+    pub fn open(tacked_dir: &Path) -> Result<FilesStore, Box<dyn Error>> {
+        let notes_dir = tacked_dir.join("notes");
+        fs::create_dir_all(&notes_dir)?;
+
+        Ok(FilesStore { notes_dir })
+    }
+
+    fn note_path(&self, id: &str) -> PathBuf {
+        self.notes_dir.join(format!("{}.json", id))
+    }
+
+    /// Reads every note whose file name starts with the prefix given, sorted
+    /// by creation time.
+    fn load(&self, prefix: &str) -> Result<Vec<Note>, Box<dyn Error>> {
+        let mut notes: Vec<Note> = Vec::new();
+        for entry in fs::read_dir(&self.notes_dir)? {
+            let path = entry?.path();
+            let is_match = match path.file_name().and_then(|f| f.to_str()) {
+                Some(name) => name.starts_with(prefix) && name.ends_with(".json"),
+                None => false,
+            };
+            if is_match {
+                notes.push(read_note(&path)?);
+            }
+        }
+        notes.sort_by(|a, b| a.datetime.cmp(&b.datetime).then(a.id.cmp(&b.id)));
+
+        Ok(notes)
+    }
+
+    fn save(&self, note: &Note) -> Result<(), Box<dyn Error>> {
+        let envelope = Envelope {
+            version: SCHEMA_VERSION,
+            note,
+        };
+        let note_json = serde_json::to_string_pretty(&envelope)? + "\n";
+        write_atomic(&self.note_path(&note.id), note_json.as_bytes())
+    }
+}
+
+impl NoteStore for FilesStore {
+    fn all(&self) -> Result<Vec<Note>, Box<dyn Error>> {
+        self.load("")
+    }
+
+    fn insert(&mut self, note: &Note) -> Result<(), Box<dyn Error>> {
+        if self.note_path(&note.id).exists() {
+            return Err(From::from(format!(
+                "A note with ID {} already exists.",
+                note.id
+            )));
+        }
+        self.save(note)
+    }
+
+    fn update(&mut self, note: &Note) -> Result<(), Box<dyn Error>> {
+        if !self.note_path(&note.id).exists() {
+            return Err(From::from(format!("No note with ID {}.", note.id)));
+        }
+        self.save(note)
+    }
+
+    fn remove(&mut self, id: &str) -> Result<(), Box<dyn Error>> {
+        fs::remove_file(self.note_path(id))
+            .map_err(|_| From::from(format!("No note with ID {}.", id)))
+    }
+
+    fn get(&self, ids: &[String]) -> Result<Vec<Note>, Box<dyn Error>> {
+        let mut notes = Vec::new();
+        for id in ids {
+            let path = self.note_path(id);
+            if path.exists() {
+                notes.push(read_note(&path)?);
+            }
+        }
+        Ok(notes)
+    }
+
+    fn find(&self, id: &str) -> Result<Note, Box<dyn Error>> {
+        let id = id.to_lowercase();
+        let mut notes = self.load(&id)?;
+        let i = find_note(&notes, &id)?;
+        Ok(notes.swap_remove(i))
+    }
+}
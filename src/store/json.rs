@@ -0,0 +1,160 @@
+//! Stores all notes in `.tacked/notes.json`, as an array inside an envelope
+//! that records the schema version.
+
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::slice;
+
+use serde_json;
+
+use note::Note;
+use store::schema::{decode_envelope, Envelope, SCHEMA_VERSION};
+use store::{lock_notes, write_atomic, NoteStore};
+
+pub struct JsonStore {
+    tacked_dir: PathBuf,
+    notes_path: PathBuf,
+}
+
+impl JsonStore {
+    pub fn new(tacked_dir: &Path) -> JsonStore {
+        JsonStore {
+            tacked_dir: tacked_dir.to_path_buf(),
+            notes_path: tacked_dir.join("notes.json"),
+        }
+    }
+
+    /// Reads every note from `notes.json`, with the schema version they were
+    /// written with.
+    fn read(&self) -> Result<(u64, Vec<Note>), Box<dyn Error>> {
+        if !self.notes_path.exists() {
+            return Ok((SCHEMA_VERSION, Vec::new()));
+        }
+        let notes_string = fs::read_to_string(&self.notes_path)?;
+
+        decode_envelope(&notes_string, &self.notes_path)
+    }
+
+    /// Reads every note from `notes.json`.
+    ///
+    /// Files from older schema versions are copied to
+    /// `notes.json.v<version>.bak` and rewritten in the current format. This
+    /// is done under the notes lock, after reading the file again in case
+    /// another process changed it first.
+    fn load(&self) -> Result<Vec<Note>, Box<dyn Error>> {
+        let (version, notes) = self.read()?;
+        if version == SCHEMA_VERSION {
+            return Ok(notes);
+        }
+        let _lock = lock_notes(&self.tacked_dir)?;
+        let (version, notes) = self.read()?;
+        if version < SCHEMA_VERSION {
+            let mut backup_path = self.notes_path.clone().into_os_string();
+            backup_path.push(format!(".v{}.bak", version));
+            fs::copy(&self.notes_path, &backup_path)?;
+            self.save(&notes)?;
+        }
+
+        Ok(notes)
+    }
+
+    /// Writes every note to `notes.json`.
+    fn save(&self, notes: &[Note]) -> Result<(), Box<dyn Error>> {
+        let notes_json = serde_json::to_string(&Envelope::new(notes))?;
+        write_atomic(&self.notes_path, notes_json.as_bytes())
+    }
+
+    /// Loads the notes, applies a change and saves them again.
+    fn modify<F>(&self, change: F) -> Result<(), Box<dyn Error>>
+    where
+        F: FnOnce(&mut Vec<Note>) -> Result<(), Box<dyn Error>>,
+    {
+        let mut notes = self.load()?;
+        change(&mut notes)?;
+        self.save(&notes)
+    }
+}
+
+/// Finds the index of the note with exactly this ID.
+fn index_of(notes: &[Note], id: &str) -> Result<usize, Box<dyn Error>> {
+    notes
+        .iter()
+        .position(|n| n.id == id)
+        .ok_or_else(|| From::from(format!("No note with ID {}.", id)))
+}
+
+impl NoteStore for JsonStore {
+    fn all(&self) -> Result<Vec<Note>, Box<dyn Error>> {
+        self.load()
+    }
+
+    fn insert(&mut self, note: &Note) -> Result<(), Box<dyn Error>> {
+        self.insert_all(slice::from_ref(note))
+    }
+
+    fn update(&mut self, note: &Note) -> Result<(), Box<dyn Error>> {
+        self.update_all(slice::from_ref(note))
+    }
+
+    fn remove(&mut self, id: &str) -> Result<(), Box<dyn Error>> {
+        self.modify(|notes| {
+            let i = index_of(notes, id)?;
+            notes.remove(i);
+            Ok(())
+        })
+    }
+
+    fn insert_all(&mut self, new_notes: &[Note]) -> Result<(), Box<dyn Error>> {
+        self.modify(|notes| {
+            notes.extend_from_slice(new_notes);
+            Ok(())
+        })
+    }
+
+    fn update_all(&mut self, changed: &[Note]) -> Result<(), Box<dyn Error>> {
+        self.modify(|notes| {
+            for note in changed {
+                let i = index_of(notes, &note.id)?;
+                notes[i] = note.clone();
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use tempdir::TempDir;
+
+    #[test]
+    fn upgrade_unversioned_file() {
+        let temp_dir = TempDir::new("json_test").expect("Could not create temp directory.");
+        let tacked_path = temp_dir.path().to_path_buf();
+        let legacy = r#"[{"user":null,"content":"Old note.","on":null,"todo":null,
+            "datetime":"2017-10-08T23:21:48.390531318+01:00"}]"#;
+        fs::write(tacked_path.join("notes.json"), legacy).unwrap();
+        let store = JsonStore::new(&tacked_path);
+        let notes = store.all().unwrap();
+        assert_eq!(notes[0].content, "Old note.");
+        let backup = fs::read_to_string(tacked_path.join("notes.json.v0.bak")).unwrap();
+        assert_eq!(backup, legacy);
+        let upgraded: Value =
+            serde_json::from_str(&fs::read_to_string(tacked_path.join("notes.json")).unwrap())
+                .unwrap();
+        assert_eq!(upgraded["version"], SCHEMA_VERSION);
+        assert_eq!(store.all().unwrap()[0].id, notes[0].id);
+    }
+
+    #[test]
+    fn refuse_newer_file() {
+        let temp_dir = TempDir::new("json_test").expect("Could not create temp directory.");
+        let tacked_path = temp_dir.path().to_path_buf();
+        let newer = format!(r#"{{"version":{},"notes":[]}}"#, SCHEMA_VERSION + 1);
+        fs::write(tacked_path.join("notes.json"), newer).unwrap();
+        let err = JsonStore::new(&tacked_path).all().unwrap_err();
+        assert!(err.to_string().contains("newer version"));
+    }
+}
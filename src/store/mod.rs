@@ -0,0 +1,367 @@
+//! This module contains the storage backends for notes and the
+//! `migrate-store` subcommand.
+
+use std::cell::RefCell;
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use clap;
+use fs2::FileExt;
+use tempfile::NamedTempFile;
+
+use config::{load_config, save_config};
+use init::find_tacked_notes;
+use note::{find_note, Note};
+
+mod files;
+mod json;
+mod schema;
+mod sqlite;
+
+pub use self::files::{ignore_local_files, FilesStore};
+pub use self::json::JsonStore;
+pub use self::schema::{decode_envelope, Envelope};
+pub use self::sqlite::SqliteStore;
+
+/// A place where the notes of a project are kept.
+///
+/// Stores do not lock themselves; callers that change notes should hold
+/// `lock_notes` from reading the notes until the change is written.
+pub trait NoteStore {
+    /// Gets every note, in the order they were created.
+    fn all(&self) -> Result<Vec<Note>, Box<dyn Error>>;
+
+    /// Adds a new note.
+    fn insert(&mut self, note: &Note) -> Result<(), Box<dyn Error>>;
+
+    /// Replaces the stored note that has the same ID.
+    fn update(&mut self, note: &Note) -> Result<(), Box<dyn Error>>;
+
+    /// Removes the note with exactly this ID.
+    fn remove(&mut self, id: &str) -> Result<(), Box<dyn Error>>;
+
+    /// Gets the note matching a partial ID.
+    fn find(&self, id: &str) -> Result<Note, Box<dyn Error>> {
+        let mut notes = self.all()?;
+        let i = find_note(&notes, id)?;
+        Ok(notes.swap_remove(i))
+    }
+
+    /// Gets the notes with exactly these IDs. Unknown IDs are skipped.
+    fn get(&self, ids: &[String]) -> Result<Vec<Note>, Box<dyn Error>> {
+        self.query(&|n| ids.contains(&n.id))
+    }
+
+    /// Gets every note that passes the filter.
+    fn query(&self, filter: &dyn Fn(&Note) -> bool) -> Result<Vec<Note>, Box<dyn Error>> {
+        let mut notes = self.all()?;
+        notes.retain(|n| filter(n));
+        Ok(notes)
+    }
+
+    /// Adds many new notes.
+    fn insert_all(&mut self, notes: &[Note]) -> Result<(), Box<dyn Error>> {
+        for note in notes {
+            self.insert(note)?;
+        }
+        Ok(())
+    }
+
+    /// Replaces many stored notes.
+    fn update_all(&mut self, notes: &[Note]) -> Result<(), Box<dyn Error>> {
+        for note in notes {
+            self.update(note)?;
+        }
+        Ok(())
+    }
+}
+
+/// The kinds of note store that a project can use.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreKind {
+    /// All notes in a single `notes.json` file.
+    #[default]
+    Json,
+    /// An SQLite database in `notes.db`.
+    Sqlite,
+    /// One file per note in the `notes` directory.
+    Files,
+}
+
+impl FromStr for StoreKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<StoreKind, String> {
+        match s {
+            "json" => Ok(StoreKind::Json),
+            "sqlite" => Ok(StoreKind::Sqlite),
+            "files" => Ok(StoreKind::Files),
+            _ => Err(format!(
+                "Unknown store '{}', expected json, sqlite or files.",
+                s
+            )),
+        }
+    }
+}
+
+/// Opens a note store of the given kind in the `.tacked` directory.
+pub fn open_store_kind(
+    kind: StoreKind,
+    tacked_dir: &Path,
+) -> Result<Box<dyn NoteStore>, Box<dyn Error>> {
+    Ok(match kind {
+        StoreKind::Json => Box::new(JsonStore::new(tacked_dir)),
+        StoreKind::Sqlite => Box::new(SqliteStore::open(tacked_dir)?),
+        StoreKind::Files => Box::new(FilesStore::open(tacked_dir)?),
+    })
+}
+
+/// Opens the note store configured for the project.
+pub fn open_store(tacked_dir: &Path) -> Result<Box<dyn NoteStore>, Box<dyn Error>> {
+    let config = load_config(tacked_dir)?;
+    open_store_kind(config.store, tacked_dir)
+}
+
+/// Main entry point to the `migrate-store` subcommand.
+pub fn run_migrate_store(input: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let cwd = Path::new(".").canonicalize()?;
+    let maybe_tacked = find_tacked_notes(&cwd)?;
+    if let Some(tacked_dir) = maybe_tacked {
+        let to: StoreKind = input
+            .value_of("to")
+            .expect("Store is a required argument.")
+            .parse()?;
+        let count = migrate_store(to, &tacked_dir)?;
+        println!("Moved {} note(s) to the {:?} store.", count, to);
+    } else {
+        return Err(From::from(
+            "No `.tacked` directory found. Run `init` before adding notes.",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Copies every note into a store of a different kind and makes it the
+/// project's store. The old store's files are kept with a `.bak` extension,
+/// and the project's config only changes once they have been moved.
+pub fn migrate_store(to: StoreKind, tacked_dir: &Path) -> Result<usize, Box<dyn Error>> {
+    let _lock = lock_notes(tacked_dir)?;
+    let mut config = load_config(tacked_dir)?;
+    let from = config.store;
+    if from == to {
+        return Err(From::from(format!(
+            "Project already uses the {:?} store.",
+            to
+        )));
+    }
+    for file_name in store_files(from) {
+        let backup = tacked_dir.join(format!("{}.bak", file_name));
+        if backup.exists() {
+            return Err(From::from(format!(
+                "{} already exists, move it out of the way before migrating.",
+                backup.display()
+            )));
+        }
+    }
+    let notes = open_store_kind(from, tacked_dir)?.all()?;
+    let mut new_store = open_store_kind(to, tacked_dir)?;
+    if !new_store.all()?.is_empty() {
+        return Err(From::from(format!(
+            "The {:?} store already contains notes, remove it before migrating.",
+            to
+        )));
+    }
+    new_store.insert_all(&notes)?;
+    for file_name in store_files(from) {
+        let path = tacked_dir.join(file_name);
+        if path.exists() {
+            fs::rename(&path, tacked_dir.join(format!("{}.bak", file_name)))?;
+        }
+    }
+    if to == StoreKind::Files {
+        ignore_local_files(tacked_dir)?;
+    }
+    config.store = to;
+    save_config(&config, tacked_dir)?;
+
+    Ok(notes.len())
+}
+
+/// The files in `.tacked` that hold the notes of a store.
+fn store_files(kind: StoreKind) -> &'static [&'static str] {
+    match kind {
+        StoreKind::Json => &["notes.json"],
+        StoreKind::Sqlite => &["notes.db"],
+        StoreKind::Files => &["notes"],
+    }
+}
+
+/// A value that changes whenever the notes of a project change, so that data
+/// derived from them can be cached. It is built from the sizes and
+/// modification times of the store's files.
+pub fn store_revision(tacked_dir: &Path) -> Result<String, Box<dyn Error>> {
+    let config = load_config(tacked_dir)?;
+    let mut revision = format!("{:?}", config.store);
+    for file_name in store_files(config.store) {
+        let path = tacked_dir.join(file_name);
+        if !path.exists() {
+            revision.push_str(":-");
+            continue;
+        }
+        let mut entries = vec![fs::metadata(&path)?];
+        if path.is_dir() {
+            for entry in fs::read_dir(&path)? {
+                entries.push(entry?.metadata()?);
+            }
+        }
+        let mut latest = SystemTime::UNIX_EPOCH;
+        let mut size = 0;
+        for metadata in entries.iter() {
+            latest = latest.max(metadata.modified()?);
+            size += metadata.len();
+        }
+        let since_epoch = latest.duration_since(SystemTime::UNIX_EPOCH)?;
+        revision.push_str(&format!(
+            ":{}:{}:{}",
+            entries.len(),
+            size,
+            since_epoch.as_nanos()
+        ));
+    }
+
+    Ok(revision)
+}
+
+thread_local! {
+    /// The `.tacked` directories this thread holds the lock on. A lock taken
+    /// again while it's held, such as by a store upgrading old notes in the
+    /// middle of a change, would otherwise wait for itself.
+    static HELD_LOCKS: RefCell<Vec<PathBuf>> = const { RefCell::new(Vec::new()) };
+}
+
+/// An exclusive lock on the notes in a `.tacked` directory, released when
+/// dropped.
+pub struct NotesLock {
+    /// The lock file and the directory it locks, or `None` if this thread
+    /// already held the lock when it was taken.
+    held: Option<(File, PathBuf)>,
+}
+
+impl Drop for NotesLock {
+    fn drop(&mut self) {
+        if let Some((_, ref dir)) = self.held {
+            HELD_LOCKS.with(|held| held.borrow_mut().retain(|d| d != dir));
+        }
+    }
+}
+
+/// Takes an advisory lock on the `.tacked` directory, waiting until any other
+/// `tack` process has finished with it. Taking the lock again on a thread
+/// that holds it returns straight away.
+///
+/// This should be held around any read-modify-write of the notes, so that
+/// concurrent changes are not lost.
+pub fn lock_notes(tacked_dir: &Path) -> Result<NotesLock, Box<dyn Error>> {
+    let dir = tacked_dir
+        .canonicalize()
+        .unwrap_or_else(|_| tacked_dir.to_path_buf());
+    if HELD_LOCKS.with(|held| held.borrow().contains(&dir)) {
+        return Ok(NotesLock { held: None });
+    }
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(tacked_dir.join("lock"))?;
+    file.lock_exclusive()?;
+    HELD_LOCKS.with(|held| held.borrow_mut().push(dir.clone()));
+
+    Ok(NotesLock {
+        held: Some((file, dir)),
+    })
+}
+
+/// Replaces the contents of a file without risking a partially written file.
+///
+/// The contents are written to a temporary file in the same directory, synced
+/// to disk and then renamed over the target, so the existing file survives a
+/// crash or a full disk.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), Box<dyn Error>> {
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let mut buffer = NamedTempFile::new_in(dir)?;
+    buffer.write_all(contents)?;
+    buffer.as_file().sync_all()?;
+    buffer.persist(path)?;
+    if let Ok(dir) = File::open(dir) {
+        // Not all platforms can sync a directory, so failure is ignored.
+        let _ = dir.sync_all();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use note::create_note;
+    use tempdir::TempDir;
+
+    #[test]
+    fn migrate_between_stores() {
+        let temp_dir = TempDir::new("store_test").expect("Could not create temp directory.");
+        let tacked_path = temp_dir.path().join(".tacked");
+        fs::create_dir(tacked_path.clone()).unwrap();
+        create_note(String::from("First."), None, None, &[], &tacked_path).unwrap();
+        create_note(String::from("Second."), None, None, &[], &tacked_path).unwrap();
+        let before = open_store(&tacked_path).unwrap().all().unwrap();
+        assert_eq!(migrate_store(StoreKind::Sqlite, &tacked_path).unwrap(), 2);
+        assert!(tacked_path.join("notes.json.bak").exists());
+        let mut store = open_store(&tacked_path).unwrap();
+        let after = store.all().unwrap();
+        assert_eq!(after[1].id, before[1].id);
+        assert_eq!(after[1].content, "Second.");
+        let mut note = store.find(&before[0].id[..5]).unwrap();
+        note.content = String::from("Changed.");
+        store.update(&note).unwrap();
+        store.remove(&before[1].id).unwrap();
+        assert_eq!(migrate_store(StoreKind::Files, &tacked_path).unwrap(), 1);
+        let note_path = tacked_path
+            .join("notes")
+            .join(format!("{}.json", before[0].id));
+        assert!(note_path.exists());
+        let ignored = fs::read_to_string(tacked_path.join(".gitignore")).unwrap();
+        assert!(ignored.lines().any(|l| l == "lock"));
+        assert_eq!(migrate_store(StoreKind::Json, &tacked_path).unwrap(), 1);
+        let after = open_store(&tacked_path).unwrap().all().unwrap();
+        assert_eq!(after.len(), 1);
+        assert_eq!(after[0].content, "Changed.");
+        // The backup of the earlier SQLite store is in the way.
+        assert!(migrate_store(StoreKind::Sqlite, &tacked_path).is_err());
+        assert_eq!(load_config(&tacked_path).unwrap().store, StoreKind::Json);
+        assert!(tacked_path.join("notes.json").exists());
+    }
+
+    #[test]
+    fn upgrade_old_notes_under_lock() {
+        let temp_dir = TempDir::new("upgrade_test").expect("Could not create temp directory.");
+        let tacked_path = temp_dir.path().join(".tacked");
+        fs::create_dir(tacked_path.clone()).unwrap();
+        let legacy = r#"[{"user":null,"content":"Old note.","on":null,"todo":null,
+            "datetime":"2017-10-08T23:21:48.390531318+01:00"}]"#;
+        fs::write(tacked_path.join("notes.json"), legacy).unwrap();
+        let first = {
+            // Upgrading inside a locked change mustn't wait on its own lock.
+            let _lock = lock_notes(&tacked_path).unwrap();
+            open_store(&tacked_path).unwrap().all().unwrap()
+        };
+        let second = open_store(&tacked_path).unwrap().all().unwrap();
+        assert_eq!(first[0].id, second[0].id);
+        assert!(tacked_path.join("notes.json.v0.bak").exists());
+    }
+}
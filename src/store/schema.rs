@@ -0,0 +1,188 @@
+//! Versioning of the stored note format.
+//!
+//! Every store records the schema version its notes were written with. Notes
+//! from older versions are upgraded by running each migration in turn, and
+//! notes from newer versions are refused rather than silently losing the
+//! fields this version doesn't know about.
+
+use std::error::Error;
+use std::path::Path;
+
+use serde_json::{self, Value};
+
+use note::Note;
+
+/// The schema version written by this version of `tack-it-on`. Any change to
+/// the fields of `Note` must increase this and add a migration.
+pub const SCHEMA_VERSION: u64 = 6;
+
+/// The top level of a file holding many notes, recording the schema version
+/// they were written with.
+#[derive(Serialize)]
+pub struct Envelope<'a> {
+    pub version: u64,
+    pub notes: &'a [Note],
+}
+
+impl<'a> Envelope<'a> {
+    /// Wraps notes in the current schema version.
+    pub fn new(notes: &'a [Note]) -> Envelope<'a> {
+        Envelope {
+            version: SCHEMA_VERSION,
+            notes,
+        }
+    }
+}
+
+/// Upgrades a note by one schema version. `MIGRATIONS[n]` upgrades a note from
+/// version `n` to `n + 1`.
+type Migration = fn(&mut Value) -> Result<(), Box<dyn Error>>;
+
+const MIGRATIONS: [Migration; SCHEMA_VERSION as usize] =
+    [v0_to_v1, v1_to_v2, v2_to_v3, v3_to_v4, v4_to_v5, v5_to_v6];
+
+/// Version 0 was a bare array of notes, which may not have an ID, completion
+/// time, modification time or tags.
+fn v0_to_v1(note: &mut Value) -> Result<(), Box<dyn Error>> {
+    let fields = note.as_object_mut().ok_or("Note is not a JSON object.")?;
+    let has_id = match fields.get("id") {
+        Some(Value::String(id)) => !id.is_empty(),
+        _ => false,
+    };
+    if !has_id {
+        fields.insert(String::from("id"), Value::from(Note::gen_id()));
+    }
+    for field in &["completed", "modified"] {
+        fields.entry(*field).or_insert(Value::Null);
+    }
+    fields
+        .entry("tags")
+        .or_insert_with(|| Value::Array(Vec::new()));
+
+    Ok(())
+}
+
+/// Version 2 added line range anchors.
+fn v1_to_v2(note: &mut Value) -> Result<(), Box<dyn Error>> {
+    let fields = note.as_object_mut().ok_or("Note is not a JSON object.")?;
+    fields.entry("anchor").or_insert(Value::Null);
+
+    Ok(())
+}
+
+/// Version 3 added due dates of to do items.
+fn v2_to_v3(note: &mut Value) -> Result<(), Box<dyn Error>> {
+    let fields = note.as_object_mut().ok_or("Note is not a JSON object.")?;
+    fields.entry("due").or_insert(Value::Null);
+
+    Ok(())
+}
+
+/// Version 4 added recurrence rules of to do items.
+fn v3_to_v4(note: &mut Value) -> Result<(), Box<dyn Error>> {
+    let fields = note.as_object_mut().ok_or("Note is not a JSON object.")?;
+    fields.entry("recur").or_insert(Value::Null);
+
+    Ok(())
+}
+
+/// Version 5 added replies to notes.
+fn v4_to_v5(note: &mut Value) -> Result<(), Box<dyn Error>> {
+    let fields = note.as_object_mut().ok_or("Note is not a JSON object.")?;
+    fields.entry("parent").or_insert(Value::Null);
+
+    Ok(())
+}
+
+/// Version 6 added authors' emails.
+fn v5_to_v6(note: &mut Value) -> Result<(), Box<dyn Error>> {
+    let fields = note.as_object_mut().ok_or("Note is not a JSON object.")?;
+    fields.entry("email").or_insert(Value::Null);
+
+    Ok(())
+}
+
+/// Checks that notes written with a schema version can be read.
+pub fn check_version(version: u64, source: &Path) -> Result<(), Box<dyn Error>> {
+    if version > SCHEMA_VERSION {
+        return Err(From::from(format!(
+            "{} was written by a newer version of tack-it-on (schema version {}, this \
+             version understands up to {}). Please upgrade tack-it-on.",
+            source.display(),
+            version,
+            SCHEMA_VERSION
+        )));
+    }
+
+    Ok(())
+}
+
+/// Reads a note written with the given schema version, upgrading it to the
+/// current version.
+pub fn decode_note(mut note: Value, version: u64) -> Result<Note, Box<dyn Error>> {
+    for migration in MIGRATIONS.iter().skip(version as usize) {
+        migration(&mut note)?;
+    }
+
+    Ok(serde_json::from_value(note)?)
+}
+
+/// Reads a file of notes written as an envelope, or as a bare array before
+/// schema versions were introduced, upgrading the notes to the current
+/// version. Returns the version the notes were written with.
+pub fn decode_envelope(contents: &str, source: &Path) -> Result<(u64, Vec<Note>), Box<dyn Error>> {
+    let (version, raw_notes) = match serde_json::from_str(contents)? {
+        Value::Array(raw_notes) => (0, raw_notes),
+        Value::Object(mut envelope) => {
+            let version = envelope
+                .get("version")
+                .and_then(Value::as_u64)
+                .ok_or_else(|| format!("{} has no schema version.", source.display()))?;
+            check_version(version, source)?;
+            match envelope.remove("notes") {
+                Some(Value::Array(raw_notes)) => (version, raw_notes),
+                _ => {
+                    return Err(From::from(format!(
+                        "{} has no notes array.",
+                        source.display()
+                    )))
+                }
+            }
+        }
+        _ => {
+            return Err(From::from(format!(
+                "{} is not a notes file.",
+                source.display()
+            )))
+        }
+    };
+    let notes = raw_notes
+        .into_iter()
+        .map(|n| decode_note(n, version))
+        .collect::<Result<Vec<Note>, _>>()?;
+
+    Ok((version, notes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upgrade_v0_note() {
+        let old = serde_json::from_str(
+            r#"{"user":null,"content":"Old note.","on":null,"todo":null,
+                "datetime":"2017-10-08T23:21:48.390531318+01:00"}"#,
+        )
+        .unwrap();
+        let note = decode_note(old, 0).unwrap();
+        assert_eq!(note.id.len(), 32);
+        assert!(note.tags.is_empty());
+        assert!(note.anchor.is_none());
+        assert!(note.due.is_none());
+        assert!(note.recur.is_none());
+        assert!(note.parent.is_none());
+        assert!(note.email.is_none());
+        assert!(check_version(SCHEMA_VERSION + 1, Path::new("notes.json")).is_err());
+    }
+}
@@ -0,0 +1,172 @@
+//! Stores notes in an SQLite database in `.tacked/notes.db`.
+//!
+//! Each note is kept as a JSON document alongside its ID, so only the notes
+//! that change are rewritten. The schema version of the documents is kept in
+//! the database's `user_version`.
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use rusqlite::{params, params_from_iter, Connection};
+use serde_json;
+
+use note::{find_note, Note};
+use store::schema::{check_version, decode_note, SCHEMA_VERSION};
+use store::{lock_notes, NoteStore};
+
+pub struct SqliteStore {
+    conn: Connection,
+    /// The schema version of the stored notes.
+    version: u64,
+}
+
+impl SqliteStore {
+    /// Opens `notes.db`, creating it if needed.
+    ///
+    /// Databases from older schema versions are copied to
+    /// `notes.db.v<version>.bak` and their notes upgraded, under the notes
+    /// lock.
+    pub fn open(tacked_dir: &Path) -> Result<SqliteStore, Box<dyn Error>> {
+        let db_path = tacked_dir.join("notes.db");
+        let conn = Connection::open(&db_path)?;
+        conn.busy_timeout(::std::time::Duration::from_secs(10))?;
+        let tables: i64 = conn.query_row(
+            "SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name = 'notes'",
+            [],
+            |row| row.get(0),
+        )?;
+        if tables == 0 {
+            conn.execute_batch(&format!(
+                "CREATE TABLE notes (
+                     id   TEXT PRIMARY KEY NOT NULL,
+                     note TEXT NOT NULL
+                 );
+                 PRAGMA user_version = {};",
+                SCHEMA_VERSION
+            ))?;
+        }
+        let version = read_version(&conn)?;
+        check_version(version, &db_path)?;
+        let mut store = SqliteStore { conn, version };
+        if version == SCHEMA_VERSION {
+            return Ok(store);
+        }
+        // Another process may have upgraded or changed the notes before the
+        // lock was free, so the version is read again under it.
+        let _lock = lock_notes(tacked_dir)?;
+        let version = read_version(&store.conn)?;
+        store.version = version;
+        if version < SCHEMA_VERSION {
+            let mut backup_path = db_path.clone().into_os_string();
+            backup_path.push(format!(".v{}.bak", version));
+            fs::copy(&db_path, &backup_path)?;
+            let notes = store.all()?;
+            store.version = SCHEMA_VERSION;
+            store.update_all(&notes)?;
+            store
+                .conn
+                .execute_batch(&format!("PRAGMA user_version = {};", SCHEMA_VERSION))?;
+        }
+
+        Ok(store)
+    }
+
+    /// Runs a query that selects the `note` column and decodes the results.
+    fn select(&self, sql: &str, param: Option<&str>) -> Result<Vec<Note>, Box<dyn Error>> {
+        let mut stmt = self.conn.prepare(sql)?;
+        let rows = stmt.query_map(params_from_iter(param.iter()), |row| {
+            row.get::<_, String>(0)
+        })?;
+        let mut notes = Vec::new();
+        for row in rows {
+            notes.push(decode_note(serde_json::from_str(&row?)?, self.version)?);
+        }
+
+        Ok(notes)
+    }
+}
+
+/// The schema version of the notes in a database.
+fn read_version(conn: &Connection) -> Result<u64, Box<dyn Error>> {
+    let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+    Ok(version as u64)
+}
+
+impl NoteStore for SqliteStore {
+    fn all(&self) -> Result<Vec<Note>, Box<dyn Error>> {
+        self.select("SELECT note FROM notes ORDER BY rowid", None)
+    }
+
+    fn insert(&mut self, note: &Note) -> Result<(), Box<dyn Error>> {
+        self.conn.execute(
+            "INSERT INTO notes (id, note) VALUES (?1, ?2)",
+            params![note.id, serde_json::to_string(note)?],
+        )?;
+        Ok(())
+    }
+
+    fn update(&mut self, note: &Note) -> Result<(), Box<dyn Error>> {
+        let changed = self.conn.execute(
+            "UPDATE notes SET note = ?2 WHERE id = ?1",
+            params![note.id, serde_json::to_string(note)?],
+        )?;
+        if changed == 0 {
+            return Err(From::from(format!("No note with ID {}.", note.id)));
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, id: &str) -> Result<(), Box<dyn Error>> {
+        let changed = self
+            .conn
+            .execute("DELETE FROM notes WHERE id = ?1", params![id])?;
+        if changed == 0 {
+            return Err(From::from(format!("No note with ID {}.", id)));
+        }
+        Ok(())
+    }
+
+    fn find(&self, id: &str) -> Result<Note, Box<dyn Error>> {
+        let id = id.to_lowercase();
+        let mut notes = self.select(
+            "SELECT note FROM notes WHERE substr(id, 1, length(?1)) = ?1 ORDER BY rowid",
+            Some(&id),
+        )?;
+        let i = find_note(&notes, &id)?;
+        Ok(notes.swap_remove(i))
+    }
+
+    fn get(&self, ids: &[String]) -> Result<Vec<Note>, Box<dyn Error>> {
+        let mut notes = Vec::new();
+        for id in ids {
+            notes.extend(self.select("SELECT note FROM notes WHERE id = ?1", Some(id))?);
+        }
+        Ok(notes)
+    }
+
+    fn insert_all(&mut self, notes: &[Note]) -> Result<(), Box<dyn Error>> {
+        let tx = self.conn.transaction()?;
+        for note in notes {
+            tx.execute(
+                "INSERT INTO notes (id, note) VALUES (?1, ?2)",
+                params![note.id, serde_json::to_string(note)?],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn update_all(&mut self, notes: &[Note]) -> Result<(), Box<dyn Error>> {
+        let tx = self.conn.transaction()?;
+        for note in notes {
+            tx.execute(
+                "UPDATE notes SET note = ?2 WHERE id = ?1",
+                params![note.id, serde_json::to_string(note)?],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,178 @@
+//! This module contains functions for tagging notes and the `tags`
+//! subcommand.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::Path;
+
+use clap;
+
+use global::resolve_tacked_dir;
+use note::{get_notes, Note};
+use store::{lock_notes, open_store};
+
+/// Filters notes by their tags.
+///
+/// A note passes if it has every tag in `all`, at least one tag in `any` (if
+/// any are given) and none of the tags in `none`.
+#[derive(Debug, Default)]
+pub struct TagFilter {
+    pub all: Vec<String>,
+    pub any: Vec<String>,
+    pub none: Vec<String>,
+}
+
+impl TagFilter {
+    /// Creates a filter from the `--tag`, `--any-tag` and `--not-tag` arguments.
+    pub fn from_args(input: &clap::ArgMatches) -> TagFilter {
+        let values = |name| match input.values_of(name) {
+            Some(vals) => vals.map(normalise_tag).collect(),
+            None => Vec::new(),
+        };
+        TagFilter {
+            all: values("tag"),
+            any: values("any_tag"),
+            none: values("not_tag"),
+        }
+    }
+
+    pub fn matches(&self, note: &Note) -> bool {
+        let has = |tag: &String| note.tags.contains(tag);
+        self.all.iter().all(has)
+            && (self.any.is_empty() || self.any.iter().any(has))
+            && !self.none.iter().any(has)
+    }
+}
+
+/// Main entry point for the `tags` subcommand.
+pub fn run_tags(input: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let tacked_dir = resolve_tacked_dir(input)?;
+    if let Some(mut names) = input.values_of("rename") {
+        let old = names.next().expect("Rename takes two values.");
+        let new = names.next().expect("Rename takes two values.");
+        let count = rename_tags(&[old], new, &tacked_dir)?;
+        println!("Renamed tag on {} note(s).", count);
+    } else if let Some(olds) = input.values_of("merge") {
+        let new = input
+            .value_of("into")
+            .ok_or("`--merge` requires a tag to merge `--into`.")?;
+        let olds: Vec<&str> = olds.collect();
+        let count = rename_tags(&olds, new, &tacked_dir)?;
+        println!("Merged tags on {} note(s).", count);
+    } else {
+        let notes = get_notes(&tacked_dir)?;
+        for (tag, count) in tag_counts(&notes) {
+            println!("{:>5} {}", count, tag);
+        }
+    }
+
+    Ok(())
+}
+
+/// Strips any leading `#` from a tag and lowercases it.
+pub fn normalise_tag(tag: &str) -> String {
+    tag.trim().trim_start_matches('#').to_lowercase()
+}
+
+/// Finds all `#hashtags` in a piece of text.
+///
+/// A hashtag must start at the beginning of the text or after whitespace and
+/// begin with a letter, so that issue numbers like `#12` are not picked up.
+pub fn extract_hashtags(content: &str) -> Vec<String> {
+    let mut tags = Vec::new();
+    for word in content.split_whitespace() {
+        if !word.starts_with('#') {
+            continue;
+        }
+        let tag: String = word[1..]
+            .chars()
+            .take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '-')
+            .collect();
+        let tag = tag.trim_end_matches('-');
+        if tag.chars().next().is_some_and(|c| c.is_alphabetic()) {
+            tags.push(normalise_tag(tag));
+        }
+    }
+
+    tags
+}
+
+/// Adds tags to a note, skipping any it already has.
+pub fn add_tags<I: IntoIterator<Item = String>>(note: &mut Note, tags: I) {
+    for tag in tags {
+        if !tag.is_empty() && !note.tags.contains(&tag) {
+            note.tags.push(tag);
+        }
+    }
+}
+
+/// Counts how many notes each tag is used on.
+pub fn tag_counts(notes: &[Note]) -> Vec<(String, usize)> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for tag in notes.iter().flat_map(|n| n.tags.iter()) {
+        *counts.entry(tag).or_insert(0) += 1;
+    }
+    let mut counts: Vec<(String, usize)> = counts
+        .into_iter()
+        .map(|(t, c)| (String::from(t), c))
+        .collect();
+    counts.sort_by(|(_, c), (_, d)| d.cmp(c));
+
+    counts
+}
+
+/// Replaces the old tags with the new tag on every note, merging them if the
+/// note already has the new tag. Returns the number of notes changed.
+pub fn rename_tags(olds: &[&str], new: &str, tacked_dir: &Path) -> Result<usize, Box<dyn Error>> {
+    let olds: Vec<String> = olds.iter().map(|t| normalise_tag(t)).collect();
+    let new = normalise_tag(new);
+    if new.is_empty() {
+        return Err(From::from("New tag name is empty."));
+    }
+    let _lock = lock_notes(tacked_dir)?;
+    let mut store = open_store(tacked_dir)?;
+    let mut changed = store.query(&|n| n.tags.iter().any(|t| olds.contains(t)))?;
+    for note in changed.iter_mut() {
+        note.tags.retain(|t| !olds.contains(t));
+        add_tags(note, vec![new.clone()]);
+    }
+    store.update_all(&changed)?;
+
+    Ok(changed.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use note::create_note;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn find_hashtags() {
+        let tags = extract_hashtags("Fix #perf in loop, see #12.\n#Bug-fix, #");
+        assert_eq!(tags, vec!["perf", "bug-fix"]);
+    }
+
+    #[test]
+    fn filter_and_rename_tags() {
+        let temp_dir = TempDir::new("tags_test").expect("Could not create temp directory.");
+        let tacked_path = temp_dir.path().join(".tacked");
+        fs::create_dir(tacked_path.clone()).unwrap();
+        let tags = vec![String::from("bug")];
+        create_note(String::from("Slow #perf"), None, None, &tags, &tacked_path).unwrap();
+        create_note(String::from("Crash"), None, None, &tags, &tacked_path).unwrap();
+        let notes = get_notes(&tacked_path).unwrap();
+        let filter = TagFilter {
+            all: vec![String::from("bug")],
+            none: vec![String::from("perf")],
+            ..Default::default()
+        };
+        let matching: Vec<&Note> = notes.iter().filter(|n| filter.matches(n)).collect();
+        assert_eq!(matching.len(), 1);
+        assert_eq!(matching[0].content, "Crash");
+        assert_eq!(rename_tags(&["perf"], "bug", &tacked_path).unwrap(), 1);
+        let notes = get_notes(&tacked_path).unwrap();
+        assert_eq!(tag_counts(&notes), vec![(String::from("bug"), 2)]);
+    }
+}
@@ -0,0 +1,202 @@
+//! Conversion of to do items to and from [Taskwarrior]'s JSON format, as used
+//! by `task export` and `task import`.
+//!
+//! Note IDs become task UUIDs. Priorities are mapped onto Taskwarrior's
+//! `H`, `M` and `L`, with the exact priority and the file the note is on kept
+//! in the `tackpriority` and `tackon` attributes. Due dates are due at the
+//! start of the day.
+//!
+//! [Taskwarrior]: https://taskwarrior.org/docs/commands/import/
+
+use std::error::Error;
+
+use chrono::{self, Local, NaiveDateTime, TimeZone, Utc};
+use serde_json::{self, Value};
+
+use note::Note;
+use tags::{add_tags, normalise_tag};
+use todotxt::start_of_day;
+
+/// The fields of a note that Taskwarrior records. Times are only recorded to
+/// the second, so aren't compared when importing.
+pub const CARRIED_FIELDS: [&str; 5] = ["content", "todo", "tags", "on", "due"];
+
+/// Taskwarrior's format for times, always in UTC.
+const TIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Task {
+    uuid: String,
+    description: String,
+    status: String,
+    entry: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    end: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    modified: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    due: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    priority: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tackpriority: Option<i8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tackon: Option<String>,
+}
+
+fn format_time(time: &chrono::DateTime<Local>) -> String {
+    time.with_timezone(&Utc).format(TIME_FORMAT).to_string()
+}
+
+fn parse_time(time: &str) -> Result<chrono::DateTime<Local>, Box<dyn Error>> {
+    let naive = NaiveDateTime::parse_from_str(time, TIME_FORMAT)
+        .map_err(|_| format!("'{}' is not a Taskwarrior time.", time))?;
+
+    Ok(Utc.from_utc_datetime(&naive).with_timezone(&Local))
+}
+
+/// Formats a note ID as a UUID with hyphens. IDs that aren't UUIDs, which
+/// Taskwarrior would reject, are left as they are.
+fn to_uuid(id: &str) -> String {
+    if id.len() != 32 || !id.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return String::from(id);
+    }
+    format!(
+        "{}-{}-{}-{}-{}",
+        &id[..8],
+        &id[8..12],
+        &id[12..16],
+        &id[16..20],
+        &id[20..]
+    )
+}
+
+/// Writes the to do items among the notes as a Taskwarrior JSON array.
+pub fn to_taskwarrior(notes: &[Note]) -> Result<String, Box<dyn Error>> {
+    let tasks: Vec<Task> = notes
+        .iter()
+        .filter_map(|note| {
+            let (priority, done) = note.todo?;
+            let level = match priority {
+                p if p >= 5 => "H",
+                3 | 4 => "M",
+                _ => "L",
+            };
+            Some(Task {
+                uuid: to_uuid(&note.id),
+                description: note.content.clone(),
+                status: String::from(if done { "completed" } else { "pending" }),
+                entry: format_time(&note.datetime),
+                end: note.completed.as_ref().filter(|_| done).map(format_time),
+                modified: note.modified.as_ref().map(format_time),
+                due: note.due.map(|due| format_time(&start_of_day(due))),
+                priority: Some(String::from(level)),
+                tags: note.tags.clone(),
+                tackpriority: Some(priority),
+                tackon: note.on.as_ref().map(|on| on.to_string_lossy().into_owned()),
+            })
+        })
+        .collect();
+
+    Ok(serde_json::to_string_pretty(&tasks)?)
+}
+
+/// Reads to do items from Taskwarrior JSON, either an array of tasks or one
+/// task per line. Deleted tasks are skipped.
+pub fn from_taskwarrior(contents: &str) -> Result<Vec<Note>, Box<dyn Error>> {
+    let tasks: Vec<Task> = match serde_json::from_str(contents) {
+        Ok(Value::Array(tasks)) => tasks
+            .into_iter()
+            .map(serde_json::from_value)
+            .collect::<Result<_, _>>()?,
+        _ => contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?,
+    };
+    let mut notes = Vec::new();
+    for task in tasks.into_iter().filter(|t| t.status != "deleted") {
+        let done = task.status == "completed";
+        let priority = task.tackpriority.unwrap_or(match task.priority.as_deref() {
+            Some("H") => 5,
+            Some("L") => 1,
+            _ => 3,
+        });
+        let id = task.uuid.replace('-', "").to_lowercase();
+        if !Note::is_valid_id(&id) {
+            return Err(From::from(format!(
+                "'{}' is not a UUID, in task '{}'.",
+                task.uuid, task.description
+            )));
+        }
+        let mut note = Note {
+            id,
+            user: None,
+            email: None,
+            content: task.description,
+            on: task.tackon.map(Into::into),
+            todo: Some((priority, done)),
+            datetime: parse_time(&task.entry)?,
+            completed: match task.end {
+                Some(ref end) if done => Some(parse_time(end)?),
+                _ => None,
+            },
+            modified: match task.modified {
+                Some(ref modified) => Some(parse_time(modified)?),
+                None => None,
+            },
+            tags: Vec::new(),
+            anchor: None,
+            due: match task.due {
+                Some(ref due) => Some(parse_time(due)?.date_naive()),
+                None => None,
+            },
+            recur: None,
+            parent: None,
+        };
+        add_tags(&mut note, task.tags.iter().map(|t| normalise_tag(t)));
+        notes.push(note);
+    }
+
+    Ok(notes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn taskwarrior_round_trip() {
+        let exported = r#"[
+            {"id":1,"uuid":"a1b2c3d4-e5f6-0718-293a-4b5c6d7e8f90","description":"Fix parser",
+             "status":"pending","entry":"20261001T120000Z","priority":"H","tags":["bug"],
+             "due":"20261023T120000Z",
+             "urgency":7.8},
+            {"uuid":"00000000-0000-0000-0000-000000000001","description":"Gone",
+             "status":"deleted","entry":"20261001T120000Z"}
+        ]"#;
+        let notes = from_taskwarrior(exported).unwrap();
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].id, "a1b2c3d4e5f60718293a4b5c6d7e8f90");
+        assert_eq!(notes[0].todo, Some((5, false)));
+        assert_eq!(notes[0].tags, vec!["bug"]);
+        let mut changed = notes.clone();
+        changed[0].todo = Some((-2, true));
+        changed[0].completed = Some(notes[0].datetime);
+        let again = from_taskwarrior(&to_taskwarrior(&changed).unwrap()).unwrap();
+        assert_eq!(again[0].id, notes[0].id);
+        assert_eq!(again[0].todo, Some((-2, true)));
+        assert_eq!(again[0].datetime, notes[0].datetime);
+        assert_eq!(again[0].completed, Some(notes[0].datetime));
+        assert!(notes[0].due.is_some());
+        assert_eq!(again[0].due, notes[0].due);
+        let escaping = r#"[{"uuid":"../../x","description":"Escape","status":"pending",
+            "entry":"20261001T120000Z"}]"#;
+        assert!(from_taskwarrior(escaping).is_err());
+        let wide = "\u{e9}".repeat(16);
+        assert_eq!(to_uuid(&wide), wide);
+    }
+}
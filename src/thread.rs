@@ -0,0 +1,189 @@
+//! This module contains the `reply` subcommand and the threads of replies it
+//! creates.
+//!
+//! A reply is a note whose `parent` is the ID of the note it answers. Replies
+//! are on the same file as their parent, so they're found alongside it.
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::path::Path;
+
+use clap;
+
+use author::current_author;
+use global::resolve_tacked_dir;
+use links::warn_dangling;
+use note::{get_content_from_editor, Note};
+use store::{lock_notes, open_store};
+use tags::{add_tags, extract_hashtags, normalise_tag};
+
+/// Main entry point for the `reply` subcommand.
+pub fn run_reply(input: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let tacked_dir = resolve_tacked_dir(input)?;
+    let id = input.value_of("id").expect("ID is a required argument.");
+    let content = match input.value_of("note") {
+        Some(content) => String::from(content),
+        None => get_content_from_editor("")?,
+    };
+    if content.split_whitespace().next().is_none() {
+        return Err(From::from("Reply has no content. Aborting."));
+    }
+    let tags: Vec<String> = match input.values_of("tag") {
+        Some(tags) => tags.map(normalise_tag).collect(),
+        None => Vec::new(),
+    };
+    let parent = reply_to(id, content.clone(), &tags, &tacked_dir)?;
+    println!("Replied to ({}).", parent.short_id());
+    warn_dangling(&content, &tacked_dir)?;
+
+    Ok(())
+}
+
+/// Adds a reply to the note with a partial ID, returning the note replied to.
+///
+/// Any `#hashtags` in the content are added to the tags supplied.
+pub fn reply_to(
+    id: &str,
+    content: String,
+    tags: &[String],
+    tacked_dir: &Path,
+) -> Result<Note, Box<dyn Error>> {
+    let _lock = lock_notes(tacked_dir)?;
+    let mut store = open_store(tacked_dir)?;
+    let parent = store.find(id)?;
+    let mut reply = Note::new(content);
+    current_author(tacked_dir).sign(&mut reply);
+    reply.on = parent.on.clone();
+    reply.anchor = parent.anchor.clone();
+    reply.parent = Some(parent.id.clone());
+    let hashtags = extract_hashtags(&reply.content);
+    add_tags(&mut reply, tags.iter().cloned().chain(hashtags));
+    store.insert(&reply)?;
+
+    Ok(parent)
+}
+
+/// Orders notes so that replies follow the note they answer, oldest first.
+/// Returns the index of each note in `notes` with its depth in its thread.
+///
+/// Replies whose parent isn't among the notes are treated as the start of a
+/// thread.
+pub fn thread_order(notes: &[&Note]) -> Vec<(usize, usize)> {
+    let ids: HashSet<&str> = notes.iter().map(|n| n.id.as_str()).collect();
+    let mut replies: HashMap<&str, Vec<usize>> = HashMap::new();
+    let mut roots = Vec::new();
+    for (i, note) in notes.iter().enumerate() {
+        match note.parent {
+            Some(ref parent) if ids.contains(parent.as_str()) => {
+                replies.entry(parent.as_str()).or_default().push(i)
+            }
+            _ => roots.push(i),
+        }
+    }
+    for children in replies.values_mut() {
+        children.sort_by_key(|i| notes[*i].datetime);
+    }
+    let mut order = Vec::new();
+    let mut visited = vec![false; notes.len()];
+    // Notes in a loop of parents have no root, so are started from last.
+    let starts: Vec<usize> = roots.into_iter().chain(0..notes.len()).collect();
+    for start in starts {
+        let mut stack = vec![(start, 0)];
+        while let Some((i, depth)) = stack.pop() {
+            if visited[i] {
+                continue;
+            }
+            visited[i] = true;
+            order.push((i, depth));
+            if let Some(children) = replies.get(notes[i].id.as_str()) {
+                stack.extend(children.iter().rev().map(|c| (*c, depth + 1)));
+            }
+        }
+    }
+
+    order
+}
+
+/// Finds the IDs of every reply to the given notes, and replies to those
+/// replies, not including the notes themselves.
+pub fn descendants(notes: &[Note], ids: &HashSet<String>) -> HashSet<String> {
+    let mut found: HashSet<String> = HashSet::new();
+    let mut frontier: Vec<&str> = ids.iter().map(String::as_str).collect();
+    while let Some(id) = frontier.pop() {
+        for note in notes.iter() {
+            if note.parent.as_deref() == Some(id)
+                && !ids.contains(&note.id)
+                && found.insert(note.id.clone())
+            {
+                frontier.push(&note.id);
+            }
+        }
+    }
+
+    found
+}
+
+/// Moves the replies to notes that are about to be removed up to the nearest
+/// note in their thread that is kept, or to the top level if there isn't
+/// one. Returns the replies that were changed.
+pub fn reparent(notes: &[Note], removed: &HashSet<String>) -> Vec<Note> {
+    let parents: HashMap<&str, Option<&String>> = notes
+        .iter()
+        .map(|n| (n.id.as_str(), n.parent.as_ref()))
+        .collect();
+    let mut changed = Vec::new();
+    for note in notes.iter().filter(|n| !removed.contains(&n.id)) {
+        let mut parent = note.parent.as_ref();
+        // Bounded, in case the parents go round in a circle.
+        for _ in 0..notes.len() {
+            match parent {
+                Some(id) if removed.contains(id) => {
+                    parent = parents.get(id.as_str()).cloned().flatten()
+                }
+                _ => break,
+            }
+        }
+        if parent != note.parent.as_ref() {
+            let mut note = note.clone();
+            note.parent = parent.filter(|id| !removed.contains(*id)).cloned();
+            changed.push(note);
+        }
+    }
+
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use note::{create_note, get_notes};
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn replies_follow_parents() {
+        let temp_dir = TempDir::new("thread_test").expect("Could not create temp directory.");
+        let tacked_path = temp_dir.path().join(".tacked");
+        fs::create_dir(tacked_path.clone()).unwrap();
+        let on = temp_dir.path().join("lib.rs");
+        fs::write(&on, "").unwrap();
+        let on = on.to_str().unwrap();
+        create_note(String::from("Slow?"), Some(on), None, &[], &tacked_path).unwrap();
+        create_note(String::from("Other."), None, None, &[], &tacked_path).unwrap();
+        let first = get_notes(&tacked_path).unwrap()[0].id.clone();
+        reply_to(&first, String::from("Yes."), &[], &tacked_path).unwrap();
+        let notes = get_notes(&tacked_path).unwrap();
+        assert_eq!(notes[2].parent.as_ref(), Some(&first));
+        assert_eq!(notes[2].on, notes[0].on);
+        reply_to(&notes[2].id, String::from("Why?"), &[], &tacked_path).unwrap();
+        let notes = get_notes(&tacked_path).unwrap();
+        let refs: Vec<&Note> = notes.iter().collect();
+        assert_eq!(thread_order(&refs), vec![(0, 0), (2, 1), (3, 2), (1, 0)]);
+        let removed: HashSet<String> = vec![notes[2].id.clone()].into_iter().collect();
+        let changed = reparent(&notes, &removed);
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].parent.as_ref(), Some(&first));
+        let removed: HashSet<String> = vec![first].into_iter().collect();
+        assert_eq!(descendants(&notes, &removed).len(), 2);
+    }
+}
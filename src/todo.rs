@@ -0,0 +1,190 @@
+//! This module contains functions for completing, reopening and reprioritising
+//! to do items.
+
+use std::error::Error;
+use std::path::Path;
+
+use chrono;
+use clap;
+
+use due::today;
+use global::resolve_tacked_dir;
+use note::{parse_priority, Note};
+use store::{lock_notes, open_store};
+
+/// Main entry point to the `done` subcommand.
+pub fn run_done(input: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let tacked_dir = resolve_tacked_dir(input)?;
+    let id = input.value_of("id").expect("ID is a required argument.");
+    match set_complete(id, true, &tacked_dir)? {
+        Some(next) => println!(
+            "Marked to do item as done. Next one ({}) is due {}.",
+            next.short_id(),
+            next.due
+                .map(|due| due.format("%a %Y-%m-%d").to_string())
+                .unwrap_or_default()
+        ),
+        None => println!("Marked to do item as done."),
+    }
+
+    Ok(())
+}
+
+/// Main entry point to the `reopen` subcommand.
+pub fn run_reopen(input: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let tacked_dir = resolve_tacked_dir(input)?;
+    let id = input.value_of("id").expect("ID is a required argument.");
+    set_complete(id, false, &tacked_dir)?;
+    println!("Reopened to do item.");
+
+    Ok(())
+}
+
+/// Main entry point to the `reprioritise` subcommand.
+pub fn run_reprioritise(input: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let tacked_dir = resolve_tacked_dir(input)?;
+    let id = input.value_of("id").expect("ID is a required argument.");
+    let priority = parse_priority(
+        input
+            .value_of("priority")
+            .expect("Priority is a required argument."),
+    )?;
+    set_priority(id, priority, &tacked_dir)?;
+    println!("Set priority of to do item to {}.", priority);
+
+    Ok(())
+}
+
+/// Marks a to do item as complete or incomplete, given a partial ID.
+///
+/// Completing a recurring item adds its next occurrence, which takes over the
+/// recurrence, and returns it.
+fn set_complete(
+    id: &str,
+    complete: bool,
+    tacked_dir: &Path,
+) -> Result<Option<Note>, Box<dyn Error>> {
+    let _lock = lock_notes(tacked_dir)?;
+    let mut store = open_store(tacked_dir)?;
+    let mut note = store.find(id)?;
+    match note.todo {
+        Some((priority, _)) => note.todo = Some((priority, complete)),
+        None => return Err(From::from("Note is not a to do item.")),
+    }
+    note.completed = if complete {
+        Some(chrono::Local::now())
+    } else {
+        None
+    };
+    let next = match note.recur {
+        Some(recur) if complete => {
+            let mut next = Note::new(note.content.clone());
+            next.user = note.user.clone();
+            next.email = note.email.clone();
+            next.on = note.on.clone();
+            next.anchor = note.anchor.clone();
+            next.tags = note.tags.clone();
+            next.todo = note.todo.map(|(priority, _)| (priority, false));
+            next.due = Some(recur.next_due(note.due, today()).ok_or_else(|| {
+                format!(
+                    "The next time ({}) repeats is beyond the calendar. Change or remove \
+                     its recurrence with `edit`.",
+                    note.short_id()
+                )
+            })?);
+            next.recur = note.recur.take();
+            Some(next)
+        }
+        _ => None,
+    };
+    store.update(&note)?;
+    if let Some(ref next) = next {
+        store.insert(next)?;
+    }
+
+    Ok(next)
+}
+
+/// Changes the priority of a to do item, given a partial ID.
+fn set_priority(id: &str, priority: i8, tacked_dir: &Path) -> Result<(), Box<dyn Error>> {
+    let _lock = lock_notes(tacked_dir)?;
+    let mut store = open_store(tacked_dir)?;
+    let mut note = store.find(id)?;
+    match note.todo {
+        Some((_, complete)) => note.todo = Some((priority, complete)),
+        None => return Err(From::from("Note is not a to do item.")),
+    }
+    store.update(&note)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use note::{create_note, get_notes};
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn complete_and_reopen() {
+        let temp_dir = TempDir::new("done_test").expect("Could not create temp directory.");
+        let tacked_path = temp_dir.path().join(".tacked");
+        fs::create_dir(tacked_path.clone()).unwrap();
+        let content = String::from("This is a test to do item.");
+        create_note(content, None, Some((3, false)), &[], &tacked_path).unwrap();
+        let notes = get_notes(&tacked_path).unwrap();
+        let id = notes[0].id.clone();
+        set_complete(&id[..6], true, &tacked_path).unwrap();
+        let notes = get_notes(&tacked_path).unwrap();
+        assert_eq!(notes[0].todo, Some((3, true)));
+        assert!(notes[0].completed.is_some());
+        set_complete(&id, false, &tacked_path).unwrap();
+        set_priority(&id, 5, &tacked_path).unwrap();
+        let notes = get_notes(&tacked_path).unwrap();
+        assert_eq!(notes[0].todo, Some((5, false)));
+        assert!(notes[0].completed.is_none());
+    }
+
+    #[test]
+    fn recurring_items_repeat() {
+        let temp_dir = TempDir::new("done_test").expect("Could not create temp directory.");
+        let tacked_path = temp_dir.path().join(".tacked");
+        fs::create_dir(tacked_path.clone()).unwrap();
+        create_note(
+            String::from("Rotate keys."),
+            None,
+            Some((4, false)),
+            &[],
+            &tacked_path,
+        )
+        .unwrap();
+        let mut notes = get_notes(&tacked_path).unwrap();
+        notes[0].due = Some(today());
+        notes[0].recur = Some("monthly".parse().unwrap());
+        open_store(&tacked_path).unwrap().update(&notes[0]).unwrap();
+        let next = set_complete(&notes[0].id, true, &tacked_path)
+            .unwrap()
+            .unwrap();
+        let notes = get_notes(&tacked_path).unwrap();
+        assert_eq!(notes.len(), 2);
+        assert_eq!(notes[0].todo, Some((4, true)));
+        assert!(notes[0].recur.is_none());
+        assert_eq!(notes[1].id, next.id);
+        assert_eq!(notes[1].todo, Some((4, false)));
+        assert_eq!(notes[1].content, "Rotate keys.");
+        assert!(notes[1].due.unwrap() > today());
+        assert_eq!(notes[1].recur, Some("monthly".parse().unwrap()));
+    }
+
+    #[test]
+    fn plain_notes_cannot_be_done() {
+        let temp_dir = TempDir::new("done_test").expect("Could not create temp directory.");
+        let tacked_path = temp_dir.path().join(".tacked");
+        fs::create_dir(tacked_path.clone()).unwrap();
+        let content = String::from("This is a test note.");
+        create_note(content, None, None, &[], &tacked_path).unwrap();
+        let notes = get_notes(&tacked_path).unwrap();
+        assert!(set_complete(&notes[0].id, true, &tacked_path).is_err());
+    }
+}
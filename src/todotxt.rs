@@ -0,0 +1,291 @@
+//! Conversion of to do items to and from the [todo.txt] format.
+//!
+//! Each to do item becomes one line, e.g.
+//!
+//! ```text
+//! (C) 2026-10-01 Fix the parser +bug @work on:src/parser.rs due:2026-10-23 id:a1b2c3d4...
+//! x 2026-10-17 2026-10-01 Write docs +docs pri:C id:e5f6a7b8...
+//! ```
+//!
+//! Priorities from 5 down to -20 become `(A)` to `(Z)`, and priorities outside
+//! that range are kept exactly with a `p:` extension. Due dates and
+//! recurrences use the common `due:` and `rec:` extensions. Tags become `+project`s,
+//! and tags starting with `@` are written as contexts. The note ID is kept in
+//! an `id:` extension so that the lines can be imported again. Only these
+//! extensions are read back, and only from the end of a line, so other words
+//! containing a colon, such as times, stay in the content.
+//!
+//! [todo.txt]: https://github.com/todotxt/todo.txt
+
+use std::error::Error;
+
+use chrono::{self, Local, NaiveDate, TimeZone};
+
+use note::{parse_priority, Note};
+use recur::Recurrence;
+use tags::{add_tags, normalise_tag};
+
+/// The fields of a note that todo.txt records. Completion times are only
+/// recorded to the day, so aren't compared when importing.
+pub const CARRIED_FIELDS: [&str; 6] = ["content", "todo", "tags", "on", "due", "recur"];
+
+/// The extensions written by `to_todotxt`.
+const EXTENSION_KEYS: [&str; 6] = ["on", "due", "rec", "pri", "p", "id"];
+
+/// The priority written as `(A)`. Each letter after `A` is one lower.
+const PRIORITY_A: i8 = 5;
+
+fn priority_letter(priority: i8) -> char {
+    let offset = (PRIORITY_A as i16 - priority as i16).clamp(0, 25);
+    (b'A' + offset as u8) as char
+}
+
+fn letter_priority(letter: char) -> i8 {
+    PRIORITY_A - (letter as u8 - b'A') as i8
+}
+
+/// Writes the to do items among the notes as todo.txt lines.
+///
+/// Items whose content ends with an extension, such as `due:friday`, are
+/// refused, as the extension would be read back in place of the item's own.
+pub fn to_todotxt(notes: &[Note]) -> Result<String, Box<dyn Error>> {
+    let mut lines: Vec<String> = Vec::new();
+    for note in notes.iter() {
+        let (priority, done) = match note.todo {
+            Some(todo) => todo,
+            None => continue,
+        };
+        let mut parts: Vec<String> = Vec::new();
+        if done {
+            parts.push(String::from("x"));
+            let completed = note.completed.unwrap_or(note.datetime);
+            parts.push(completed.format("%Y-%m-%d").to_string());
+        } else {
+            parts.push(format!("({})", priority_letter(priority)));
+        }
+        parts.push(note.datetime.format("%Y-%m-%d").to_string());
+        let words: Vec<&str> = note.content.split_whitespace().collect();
+        let clash = words
+            .iter()
+            .rev()
+            .take_while(|w| is_trailing_word(w))
+            .find(|w| extension(w).is_some());
+        if let Some(word) = clash {
+            return Err(From::from(format!(
+                "Could not write ({}) as todo.txt: its content ends with '{}', which \
+                 would be read back as an extension.",
+                note.short_id(),
+                word
+            )));
+        }
+        parts.push(words.join(" "));
+        for tag in note.tags.iter() {
+            let tag = tag.split_whitespace().collect::<Vec<&str>>().join("_");
+            if tag.starts_with('@') {
+                parts.push(tag);
+            } else {
+                parts.push(format!("+{}", tag));
+            }
+        }
+        if let Some(ref on) = note.on {
+            parts.push(format!("on:{}", on.display()));
+        }
+        if let Some(ref due) = note.due {
+            parts.push(format!("due:{}", due.format("%Y-%m-%d")));
+        }
+        if let Some(ref recur) = note.recur {
+            parts.push(format!("rec:{}", recur.to_todotxt()));
+        }
+        if done {
+            parts.push(format!("pri:{}", priority_letter(priority)));
+        }
+        if !(PRIORITY_A - 25..=PRIORITY_A).contains(&priority) {
+            parts.push(format!("p:{}", priority));
+        }
+        parts.push(format!("id:{}", note.id));
+        lines.push(parts.join(" "));
+    }
+
+    Ok(lines.join("\n"))
+}
+
+/// Reads to do items from todo.txt lines.
+///
+/// Lines without an `id:` extension are given a new ID.
+pub fn from_todotxt(contents: &str) -> Result<Vec<Note>, Box<dyn Error>> {
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(parse_line)
+        .collect()
+}
+
+fn parse_date(word: Option<&&str>) -> Option<NaiveDate> {
+    word.and_then(|w| NaiveDate::parse_from_str(w, "%Y-%m-%d").ok())
+}
+
+/// Midnight at the start of a date, in local time.
+pub fn start_of_day(date: NaiveDate) -> chrono::DateTime<Local> {
+    Local
+        .from_local_datetime(
+            &date
+                .and_hms_opt(0, 0, 0)
+                .expect("Midnight is a valid time."),
+        )
+        .earliest()
+        .unwrap_or_else(Local::now)
+}
+
+fn parse_line(line: &str) -> Result<Note, Box<dyn Error>> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let mut pos = 0;
+    let done = words[0] == "x";
+    let mut completed = None;
+    let mut letter = None;
+    if done {
+        pos += 1;
+        if let Some(date) = parse_date(words.get(pos)) {
+            completed = Some(start_of_day(date));
+            pos += 1;
+        }
+    } else if let [b'(', l @ b'A'..=b'Z', b')'] = words[0].as_bytes() {
+        letter = Some(*l as char);
+        pos += 1;
+    }
+    let mut created = None;
+    if let Some(date) = parse_date(words.get(pos)) {
+        created = Some(start_of_day(date));
+        pos += 1;
+    }
+    // Tags and extensions at the end of the line aren't part of the content.
+    let mut end = words.len();
+    while end > pos && is_trailing_word(words[end - 1]) {
+        end -= 1;
+    }
+    let mut note = Note {
+        id: Note::gen_id(),
+        user: None,
+        email: None,
+        content: words[pos..end].join(" "),
+        on: None,
+        todo: None,
+        datetime: created.unwrap_or_else(Local::now),
+        completed,
+        modified: None,
+        tags: Vec::new(),
+        anchor: None,
+        due: None,
+        recur: None,
+        parent: None,
+    };
+    let mut priority = None;
+    for (i, word) in words.iter().enumerate().skip(pos) {
+        if let Some(tag) = word.strip_prefix('+') {
+            add_tags(&mut note, Some(normalise_tag(tag)));
+        } else if word.starts_with('@') && word.len() > 1 {
+            add_tags(&mut note, Some(word.to_lowercase()));
+        } else if let (true, Some((key, value))) = (i >= end, extension(word)) {
+            match key {
+                "id" => {
+                    note.id = value.to_lowercase();
+                    if !Note::is_valid_id(&note.id) {
+                        return Err(From::from(format!(
+                            "'{}' is not a note ID, expected hexadecimal.",
+                            value
+                        )));
+                    }
+                }
+                "on" => note.on = Some(value.into()),
+                "due" => {
+                    note.due = Some(parse_date(Some(&value)).ok_or_else(|| {
+                        format!("'{}' is not a due date, expected YYYY-MM-DD.", value)
+                    })?)
+                }
+                "rec" => {
+                    note.recur = Some(Recurrence::from_todotxt(value).ok_or_else(|| {
+                        format!("'{}' is not a recurrence, expected e.g. rec:2w.", value)
+                    })?)
+                }
+                "pri" => letter = value.chars().next(),
+                "p" => priority = Some(parse_priority(value)?),
+                _ => (),
+            }
+        }
+    }
+    let priority = priority
+        .or_else(|| letter.filter(char::is_ascii_uppercase).map(letter_priority))
+        .unwrap_or(3);
+    note.todo = Some((priority, done));
+    if note.content.is_empty() {
+        return Err(From::from(format!(
+            "To do item has no content: '{}'.",
+            line
+        )));
+    }
+
+    Ok(note)
+}
+
+/// Splits one of the `key:value` extensions this module writes.
+fn extension(word: &str) -> Option<(&str, &str)> {
+    let (key, value) = word.split_once(':')?;
+    if EXTENSION_KEYS.contains(&key) && !value.is_empty() {
+        Some((key, value))
+    } else {
+        None
+    }
+}
+
+fn is_trailing_word(word: &str) -> bool {
+    (word.len() > 1 && (word.starts_with('+') || word.starts_with('@')))
+        || extension(word).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn todotxt_round_trip() {
+        let line = "(B) 2026-10-01 Call Mom about +Family plans +bug @phone on:src/a.rs due:2026-10-23 rec:+2w id:abc123";
+        let notes =
+            from_todotxt(&format!("{}\n\nx 2026-10-17 Done thing pri:A p:9\n", line)).unwrap();
+        assert_eq!(notes.len(), 2);
+        assert_eq!(notes[0].id, "abc123");
+        assert_eq!(notes[0].content, "Call Mom about +Family plans");
+        assert_eq!(notes[0].todo, Some((4, false)));
+        assert_eq!(notes[0].tags, vec!["family", "bug", "@phone"]);
+        assert_eq!(notes[0].on, Some(PathBuf::from("src/a.rs")));
+        assert_eq!(notes[0].due, NaiveDate::from_ymd_opt(2026, 10, 23));
+        assert_eq!(notes[1].todo, Some((9, true)));
+        assert!(notes[1].completed.is_some());
+        let exported = to_todotxt(&notes).unwrap();
+        let again = from_todotxt(&exported).unwrap();
+        assert_eq!(again[0].id, "abc123");
+        assert_eq!(again[0].content, notes[0].content);
+        assert_eq!(again[0].tags, notes[0].tags);
+        assert_eq!(again[0].datetime, notes[0].datetime);
+        assert_eq!(again[0].due, notes[0].due);
+        assert_eq!(again[0].recur, Some("every 2 weeks".parse().unwrap()));
+        assert_eq!(again[1].todo, Some((9, true)));
+        assert_eq!(again[1].completed, notes[1].completed);
+    }
+
+    #[test]
+    fn colons_in_content_survive() {
+        let notes =
+            from_todotxt("(A) Meet at 10:30 see https://example.com x:y id:abc123").unwrap();
+        assert_eq!(
+            notes[0].content,
+            "Meet at 10:30 see https://example.com x:y"
+        );
+        let again = from_todotxt(&to_todotxt(&notes).unwrap()).unwrap();
+        assert_eq!(again[0].content, notes[0].content);
+        assert_eq!(again[0].id, "abc123");
+        let mut clash = Note::new(String::from("Ask about id:1234 +work"));
+        clash.todo = Some((3, false));
+        assert!(to_todotxt(&[clash]).is_err());
+        assert!(from_todotxt("Escape id:../../x").is_err());
+    }
+}
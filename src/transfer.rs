@@ -0,0 +1,394 @@
+//! This module contains the `export` and `import` subcommands, which move
+//! notes between projects.
+//!
+//! Exported notes are written in the same versioned format as `notes.json`,
+//! so exports from older versions of `tack-it-on` are upgraded when imported.
+//! To do items can also be moved to and from todo.txt and Taskwarrior, and
+//! exported to calendars as iCalendar.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use chrono;
+use clap;
+use serde_json::{self, Value};
+
+use global::resolve_tacked_dir;
+use ical::to_ical;
+use note::{get_notes, Note};
+use query::Query;
+use store::{decode_envelope, lock_notes, open_store, Envelope};
+use taskwarrior::{self, from_taskwarrior, to_taskwarrior};
+use todotxt::{self, from_todotxt, to_todotxt};
+
+/// The formats notes can be exported and imported in.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TransferFormat {
+    /// Every note, in the versioned format of `notes.json`.
+    #[default]
+    Json,
+    /// To do items as todo.txt lines.
+    TodoTxt,
+    /// To do items as Taskwarrior JSON.
+    Taskwarrior,
+    /// To do items as iCalendar, which can only be exported. `events` writes
+    /// dated items as all-day events rather than to do items.
+    Ics { events: bool },
+}
+
+impl TransferFormat {
+    /// The fields of a note that the format records. Only these fields are
+    /// compared and updated when notes that are already present are imported.
+    /// `None` means every field.
+    fn carried_fields(self) -> Option<&'static [&'static str]> {
+        match self {
+            TransferFormat::Json => None,
+            TransferFormat::TodoTxt => Some(&todotxt::CARRIED_FIELDS),
+            TransferFormat::Taskwarrior => Some(&taskwarrior::CARRIED_FIELDS),
+            TransferFormat::Ics { .. } => Some(&[]),
+        }
+    }
+}
+
+impl FromStr for TransferFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<TransferFormat, String> {
+        match s {
+            "json" => Ok(TransferFormat::Json),
+            "todotxt" => Ok(TransferFormat::TodoTxt),
+            "taskwarrior" => Ok(TransferFormat::Taskwarrior),
+            "ics" => Ok(TransferFormat::Ics { events: false }),
+            _ => Err(format!(
+                "Unknown format '{}', expected json, todotxt, taskwarrior or ics.",
+                s
+            )),
+        }
+    }
+}
+
+/// Main entry point for the `export` subcommand.
+pub fn run_export(input: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let tacked_dir = resolve_tacked_dir(input)?;
+    let query = match input.value_of("filter") {
+        Some(filter) => Some(Query::parse(filter)?),
+        None => None,
+    };
+    let mut format: TransferFormat = input.value_of("format").unwrap_or("json").parse()?;
+    if let TransferFormat::Ics { ref mut events } = format {
+        *events = input.is_present("events");
+    }
+    let exported = export_notes(query.as_ref(), format, &tacked_dir)?;
+    // iCalendar lines already end with CRLF.
+    if exported.ends_with('\n') {
+        print!("{}", exported);
+    } else {
+        println!("{}", exported);
+    }
+
+    Ok(())
+}
+
+/// Main entry point for the `import` subcommand.
+pub fn run_import(input: &clap::ArgMatches) -> Result<(), Box<dyn Error>> {
+    let tacked_dir = resolve_tacked_dir(input)?;
+    let file = Path::new(
+        input
+            .value_of("file")
+            .expect("File is a required argument."),
+    );
+    let contents =
+        fs::read_to_string(file).map_err(|_| format!("Could not read '{}'.", file.display()))?;
+    let format: TransferFormat = input.value_of("format").unwrap_or("json").parse()?;
+    let notes = match format {
+        TransferFormat::Json => decode_envelope(&contents, file)?.1,
+        TransferFormat::TodoTxt => from_todotxt(&contents)?,
+        TransferFormat::Taskwarrior => from_taskwarrior(&contents)?,
+        TransferFormat::Ics { .. } => {
+            return Err(From::from("iCalendar files can only be exported."))
+        }
+    };
+    let options = ImportOptions {
+        rebase: Rebase {
+            strip: input.value_of("strip_prefix").map(PathBuf::from),
+            prefix: input.value_of("prefix").map(PathBuf::from),
+        },
+        fields: format.carried_fields(),
+        update: input.is_present("update"),
+    };
+    let report = import_notes(notes, &options, &tacked_dir)?;
+    println!(
+        "Imported {} note(s), updated {}, skipped {} already present.",
+        report.imported, report.updated, report.duplicates
+    );
+    if !report.conflicts.is_empty() {
+        println!(
+            "{} note(s) differ from the notes already here with the same ID and were not \
+             imported. Use `--update` to replace them:",
+            report.conflicts.len()
+        );
+        for (note, fields) in report.conflicts.iter() {
+            println!("    {} differs in {}", note.oneliner(), fields.join(", "));
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the notes matching an optional filter in an export format.
+pub fn export_notes(
+    query: Option<&Query>,
+    format: TransferFormat,
+    tacked_dir: &Path,
+) -> Result<String, Box<dyn Error>> {
+    let mut notes = get_notes(tacked_dir)?;
+    if let Some(query) = query {
+        notes.retain(|n| query.matches(n));
+    }
+
+    match format {
+        TransferFormat::Json => Ok(serde_json::to_string_pretty(&Envelope::new(&notes))?),
+        TransferFormat::TodoTxt => to_todotxt(&notes),
+        TransferFormat::Taskwarrior => to_taskwarrior(&notes),
+        TransferFormat::Ics { events } => {
+            let project_dir = tacked_dir.parent().expect("`.tacked` has no parent dir.");
+            Ok(to_ical(&notes, project_dir, events))
+        }
+    }
+}
+
+/// How the `on` paths of imported notes are changed, e.g. when a directory
+/// of one repository becomes a repository of its own.
+#[derive(Debug, Default)]
+pub struct Rebase {
+    /// Removed from the start of paths. Paths without it are left alone.
+    pub strip: Option<PathBuf>,
+    /// Added to the start of paths, after `strip` is removed.
+    pub prefix: Option<PathBuf>,
+}
+
+impl Rebase {
+    fn apply(&self, on: &Path) -> PathBuf {
+        // Absolute paths belong to global notes, which aren't in a project.
+        if on.is_absolute() {
+            return on.to_path_buf();
+        }
+        let stripped = match self.strip {
+            Some(ref strip) => on.strip_prefix(strip).unwrap_or(on),
+            None => on,
+        };
+        match self.prefix {
+            Some(ref prefix) => prefix.join(stripped),
+            None => stripped.to_path_buf(),
+        }
+    }
+}
+
+/// Options controlling how notes are imported.
+#[derive(Debug, Default)]
+pub struct ImportOptions {
+    pub rebase: Rebase,
+    /// The fields recorded by the import format, or `None` for every field.
+    pub fields: Option<&'static [&'static str]>,
+    /// Replaces notes that differ from the imported version rather than
+    /// reporting them as conflicts.
+    pub update: bool,
+}
+
+/// The outcome of an import.
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub imported: usize,
+    pub updated: usize,
+    /// Notes that were already present, unchanged.
+    pub duplicates: usize,
+    /// Notes whose ID is already used by a different version of the note,
+    /// along with the fields that differ.
+    pub conflicts: Vec<(Note, Vec<String>)>,
+}
+
+/// Merges notes into the store, skipping those that are already present.
+///
+/// Notes with the same ID as an existing note but different contents are
+/// reported as conflicts and not imported, so that nothing is overwritten,
+/// unless `update` is set. Nothing is imported if any note has an invalid ID.
+pub fn import_notes(
+    notes: Vec<Note>,
+    options: &ImportOptions,
+    tacked_dir: &Path,
+) -> Result<ImportReport, Box<dyn Error>> {
+    for (i, note) in notes.iter().enumerate() {
+        if !Note::is_valid_id(&note.id) {
+            return Err(From::from(format!(
+                "Note {} of the import, '{}', has an invalid ID {:?}. IDs are lowercase \
+                 hexadecimal.",
+                i + 1,
+                note.content.lines().next().unwrap_or(""),
+                note.id
+            )));
+        }
+    }
+    let _lock = lock_notes(tacked_dir)?;
+    let mut store = open_store(tacked_dir)?;
+    let existing: HashMap<String, Note> = store
+        .all()?
+        .into_iter()
+        .map(|n| (n.id.clone(), n))
+        .collect();
+    let mut report = ImportReport::default();
+    let mut new_notes: Vec<Note> = Vec::new();
+    let mut changed_notes: Vec<Note> = Vec::new();
+    for mut note in notes.into_iter() {
+        note.on = note.on.map(|on| options.rebase.apply(&on));
+        let seen = |n: &Note| n.id == note.id;
+        if new_notes.iter().any(seen) || changed_notes.iter().any(seen) {
+            report.duplicates += 1;
+            continue;
+        }
+        let current = match existing.get(&note.id) {
+            Some(current) => current,
+            None => {
+                new_notes.push(note);
+                continue;
+            }
+        };
+        let merged = match options.fields {
+            Some(fields) => merge_fields(current, &note, fields)?,
+            None => note,
+        };
+        let fields = differing_fields(current, &merged);
+        if fields.is_empty() {
+            report.duplicates += 1;
+        } else if options.update {
+            changed_notes.push(merged);
+        } else {
+            report.conflicts.push((merged, fields));
+        }
+    }
+    store.insert_all(&new_notes)?;
+    store.update_all(&changed_notes)?;
+    report.imported = new_notes.len();
+    report.updated = changed_notes.len();
+
+    Ok(report)
+}
+
+/// Copies the given fields of an imported note onto an existing note.
+///
+/// Formats that record times less precisely than `tack-it-on` don't carry the
+/// completion time, so it is kept unless the item has only now been completed.
+fn merge_fields(current: &Note, imported: &Note, fields: &[&str]) -> Result<Note, Box<dyn Error>> {
+    let mut merged = serde_json::to_value(current)?;
+    let imported_value = serde_json::to_value(imported)?;
+    for field in fields.iter() {
+        merged[*field] = imported_value[*field].clone();
+    }
+    let mut merged: Note = serde_json::from_value(merged)?;
+    let was_done = current.todo.is_some_and(|(_, done)| done);
+    merged.completed = match merged.todo {
+        Some((_, true)) if was_done => current.completed,
+        Some((_, true)) => imported.completed.or_else(|| Some(chrono::Local::now())),
+        _ => None,
+    };
+
+    Ok(merged)
+}
+
+/// Lists the fields that differ between two versions of a note.
+fn differing_fields(a: &Note, b: &Note) -> Vec<String> {
+    let (a, b) = match (serde_json::to_value(a), serde_json::to_value(b)) {
+        (Ok(Value::Object(a)), Ok(Value::Object(b))) => (a, b),
+        _ => return vec![String::from("note")],
+    };
+    a.iter()
+        .filter(|(field, value)| b.get(*field) != Some(value))
+        .map(|(field, _)| field.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use note::create_note;
+    use tempdir::TempDir;
+
+    #[test]
+    fn export_and_import() {
+        let temp_dir = TempDir::new("transfer_test").expect("Could not create temp directory.");
+        let from_path = temp_dir.path().join("from").join(".tacked");
+        let to_path = temp_dir.path().join("to").join(".tacked");
+        fs::create_dir_all(from_path.clone()).unwrap();
+        fs::create_dir_all(to_path.clone()).unwrap();
+        let lib = temp_dir.path().join("from").join("lib.rs");
+        fs::write(&lib, "").unwrap();
+        let lib = lib.to_str().unwrap();
+        create_note(String::from("On lib."), Some(lib), None, &[], &from_path).unwrap();
+        create_note(String::from("Bug."), None, None, &[], &from_path).unwrap();
+        let query = Query::parse("on:lib.rs").unwrap();
+        let exported = export_notes(Some(&query), TransferFormat::Json, &from_path).unwrap();
+        let (_, notes) = decode_envelope(&exported, Path::new("export.json")).unwrap();
+        assert_eq!(notes.len(), 1);
+        let options = ImportOptions {
+            rebase: Rebase {
+                prefix: Some(PathBuf::from("crates/from")),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let report = import_notes(notes.clone(), &options, &to_path).unwrap();
+        assert_eq!(report.imported, 1);
+        let imported = get_notes(&to_path).unwrap();
+        assert_eq!(imported[0].id, notes[0].id);
+        assert_eq!(imported[0].on, Some(PathBuf::from("crates/from/lib.rs")));
+        let report = import_notes(notes.clone(), &options, &to_path).unwrap();
+        assert_eq!((report.imported, report.duplicates), (0, 1));
+        let mut diverged = notes;
+        diverged[0].content = String::from("Changed.");
+        let report = import_notes(diverged, &options, &to_path).unwrap();
+        assert_eq!(report.conflicts[0].1, vec![String::from("content")]);
+        assert_eq!(get_notes(&to_path).unwrap()[0].content, "On lib.");
+        let mut escaping = vec![Note::new(String::from("Escape."))];
+        escaping[0].id = String::from("../../x");
+        assert!(import_notes(escaping.clone(), &options, &to_path).is_err());
+        escaping[0].id = String::new();
+        assert!(import_notes(escaping, &options, &to_path).is_err());
+        assert_eq!(get_notes(&to_path).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn update_from_todotxt() {
+        let temp_dir = TempDir::new("transfer_test").expect("Could not create temp directory.");
+        let tacked_path = temp_dir.path().join(".tacked");
+        fs::create_dir(tacked_path.clone()).unwrap();
+        create_note(String::from("Plain."), None, None, &[], &tacked_path).unwrap();
+        create_note(
+            String::from("Fix it."),
+            None,
+            Some((3, false)),
+            &[],
+            &tacked_path,
+        )
+        .unwrap();
+        let exported = export_notes(None, TransferFormat::TodoTxt, &tacked_path).unwrap();
+        assert_eq!(exported.lines().count(), 1);
+        let options = ImportOptions {
+            fields: TransferFormat::TodoTxt.carried_fields(),
+            update: true,
+            ..Default::default()
+        };
+        let notes = from_todotxt(&exported).unwrap();
+        let report = import_notes(notes, &options, &tacked_path).unwrap();
+        assert_eq!((report.imported, report.duplicates), (0, 1));
+        let done = format!("x 2026-10-17 {}", &exported[4..]);
+        let notes = from_todotxt(&done).unwrap();
+        let report = import_notes(notes, &options, &tacked_path).unwrap();
+        assert_eq!(report.updated, 1);
+        let note = get_notes(&tacked_path).unwrap().pop().unwrap();
+        assert_eq!(note.todo, Some((3, true)));
+        assert!(note.completed.is_some());
+        assert_eq!(note.content, "Fix it.");
+    }
+}
@@ -0,0 +1,112 @@
+//! This module renders notes as a tree following the project's directory
+//! structure, for `show --tree`.
+
+use std::collections::BTreeMap;
+use std::path::Component;
+
+use note::Note;
+
+/// A file or directory in the tree, with the notes tacked onto it.
+#[derive(Default)]
+struct Branch<'a> {
+    children: BTreeMap<String, Branch<'a>>,
+    notes: Vec<&'a Note>,
+}
+
+impl<'a> Branch<'a> {
+    /// The number of notes on this branch and everything beneath it.
+    fn count(&self) -> usize {
+        self.notes.len() + self.children.values().map(Branch::count).sum::<usize>()
+    }
+
+    fn render(&self, prefix: &str, lines: &mut Vec<String>) {
+        for note in self.notes.iter() {
+            let connector = if self.children.is_empty() {
+                "   "
+            } else {
+                "│  "
+            };
+            lines.push(format!("{}{}{}", prefix, connector, note.oneliner()));
+        }
+        let last = self.children.len().saturating_sub(1);
+        for (i, (name, child)) in self.children.iter().enumerate() {
+            let (connector, indent) = if i == last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            let slash = if child.children.is_empty() { "" } else { "/" };
+            lines.push(format!(
+                "{}{}{}{} ({})",
+                prefix,
+                connector,
+                name,
+                slash,
+                child.count()
+            ));
+            child.render(&format!("{}{}", prefix, indent), lines);
+        }
+    }
+}
+
+/// Renders notes as a tree of the files and directories they're tacked onto,
+/// with the number of notes beneath each directory. Only paths with notes are
+/// shown, and notes that aren't on a path are listed at the top.
+pub fn render_tree(notes: &[Note]) -> Vec<String> {
+    let mut root = Branch::default();
+    for note in notes.iter() {
+        let mut branch = &mut root;
+        if let Some(ref on) = note.on {
+            for component in on.components() {
+                let name = match component {
+                    Component::RootDir => String::from(""),
+                    Component::CurDir => continue,
+                    _ => component.as_os_str().to_string_lossy().into_owned(),
+                };
+                branch = branch.children.entry(name).or_default();
+            }
+        }
+        branch.notes.push(note);
+    }
+    let mut lines = vec![format!("./ ({})", root.count())];
+    root.render("", &mut lines);
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use note::{create_note, get_notes};
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn render_note_tree() {
+        let temp_dir = TempDir::new("tree_test").expect("Could not create temp directory.");
+        let tacked_path = temp_dir.path().join(".tacked");
+        fs::create_dir(tacked_path.clone()).unwrap();
+        let src = temp_dir.path().join("src");
+        fs::create_dir(&src).unwrap();
+        fs::write(src.join("a.rs"), "").unwrap();
+        fs::write(temp_dir.path().join("README.md"), "").unwrap();
+        let on_a = src.join("a.rs");
+        let on_readme = temp_dir.path().join("README.md");
+        for (content, on) in [
+            ("First.", Some(&on_a)),
+            ("Second.", Some(&on_a)),
+            ("Read me.", Some(&on_readme)),
+            ("General.", None),
+        ] {
+            let on = on.map(|p| p.to_str().unwrap());
+            create_note(String::from(content), on, None, &[], &tacked_path).unwrap();
+        }
+        let lines = render_tree(&get_notes(&tacked_path).unwrap());
+        assert_eq!(lines[0], "./ (4)");
+        assert!(lines[1].starts_with("│  (") && lines[1].contains("General."));
+        assert_eq!(lines[2], "├── README.md (1)");
+        assert_eq!(lines[4], "└── src/ (2)");
+        assert_eq!(lines[5], "    └── a.rs (2)");
+        assert!(lines[6].starts_with("           (") && lines[7].contains("Second."));
+    }
+}